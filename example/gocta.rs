@@ -18,74 +18,10 @@ use select::document::Document;
 use select::predicate::{Attr, Class};
 use itertools::Itertools;
 
-use std::fs::File;
 use std::path::PathBuf;
 
 use futures::prelude::*;
-use serde::de;
-use serde::Deserialize;
-use khipu::{rt, Credentials, Glyph};
-
-#[derive(Deserialize)]
-#[serde(untagged)]
-enum StreamMessage {
-    Tweet(Tweet),
-    Other(de::IgnoredAny),
-}
-
-#[derive(Deserialize)]
-struct Tweet {
-    created_at: String,
-    entities: Entities,
-    id: i64,
-    text: String,
-    user: User,
-}
-
-#[derive(Deserialize)]
-struct Entities {
-    user_mentions: Vec<UserMention>,
-}
-
-#[derive(Deserialize)]
-struct UserMention {
-    id: i64,
-}
-
-#[derive(Deserialize)]
-struct User {
-    id: i64,
-    screen_name: String,
-}
-
-#[derive(Deserialize)]
-#[serde(remote = "Glyph")]
-struct GlyphDef {
-    #[serde(flatten)]
-    #[serde(with = "Consumer")]
-    client: Credentials,
-    #[serde(flatten)]
-    #[serde(with = "Access")]
-    glyph: Credentials,
-}
-
-#[derive(Deserialize)]
-#[serde(remote = "Credentials")]
-struct Consumer {
-    #[serde(rename = "consumer_key")]
-    identifier: String,
-    #[serde(rename = "consumer_secret")]
-    secret: String,
-}
-
-#[derive(Deserialize)]
-#[serde(remote = "Credentials")]
-struct Access {    
-    #[serde(rename = "access_key")]
-    identifier: String,
-    #[serde(rename = "access_secret")]
-    secret: String,
-}
+use khipu::{rt, Glyph, StreamMessage, TwitterStream};
 
 #[rt::main]
 async fn main() {
@@ -97,14 +33,14 @@ async fn main() {
     credential_path.pop();
     credential_path.push("credential.json");
 
-    let credential = File::open(credential_path).unwrap();
-    let glyph = GlyphDef::deserialize(&mut json::Deserializer::from_reader(credential)).unwrap();
+    let glyph = Glyph::from_file(credential_path).unwrap();
 
     let stream = khipu::StreamBuilder::filter(glyph.as_ref())
         .track(&['twitter', 'facebook', 'google', 'travel', 'art', 'music', 'photography', 'love', 'fashion', 'food'])
 	
         .listen()
         .unwrap()
+        .map_ok(TwitterStream::messages)
         .try_flatten_stream();
     let rest = tweetust::TwitterClient::new(
         glyph,
@@ -120,14 +56,14 @@ async fn main() {
         .object;
 
     stream
-        .try_for_each(move |json| {
-            if let Ok(StreamMessage::Tweet(tweet)) = json::from_str(&json) {
-                if tweet.user.id != user.id
+        .try_for_each(move |msg| {
+            if let StreamMessage::Tweet(tweet) = msg {
+                if tweet.user.id as i64 != user.id
                     && tweet
                         .entities
                         .user_mentions
                         .iter()
-                        .any(|mention| mention.id == user.id)
+                        .any(|mention| mention.id as i64 == user.id)
                 {
                     println!(
                         "On {}, @{} tweeted: {:?}",
@@ -137,7 +73,7 @@ async fn main() {
                     let response = format!("@{} {}", tweet.user.screen_name, tweet.text);
                     rest.statuses()
                         .update(response)
-                        .in_reply_to_status_id(tweet.id)
+                        .in_reply_to_status_id(tweet.id as i64)
                         .execute()
                         .unwrap();
                 }
@@ -0,0 +1,853 @@
+//! Alerts on sudden moves of the sentiment of tracked keywords.
+//!
+//! An `Alerter` records the scores of Tweets in its own `Aggregator` and evaluates a set of
+//! `Rule`s against the statistics of each keyword as they change:
+//!
+//! * `Rule::mean_above` and `Rule::mean_below` fire when the mean compound score of a window
+//!   crosses a level,
+//! * `Rule::z_score` fires when the mean compound score of the current period of a tumbling
+//!   window is unusual compared to the trailing periods,
+//! * `Rule::volume_burst` fires when the volume of the current period of a tumbling window is a
+//!   multiple of the mean volume of the trailing periods.
+//!
+//! Each rule has a trigger level and a clear level. An `Alert` is emitted with
+//! `AlertState::Triggered` when the rule reaches the trigger level, and with
+//! `AlertState::Resolved` once it gets back past the clear level, so that a value hovering around
+//! the trigger level does not raise an alert with each Tweet. Rules on tumbling windows are
+//! evaluated on the current period as it fills up, but are only resolved once a period closes,
+//! as the first few Tweets of a period say little about it. The periods of a keyword close when
+//! a later Tweet of the keyword is recorded, or when time is let pass with `Alerter::advance`
+//! (`AlertStream::advance` for a stream), which should be called regularly so that quiet
+//! keywords are resolved too.
+//!
+//! Alerts carry their evidence: the statistics of the window, the trailing baseline if the rule
+//! has one, and the latest Tweets of the keyword.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::alert::{Alerter, Rule};
+//! use khipu::filters::FilterParams;
+//! use khipu::matcher::TrackMatcher;
+//! use khipu::window::{Window, FIVE_MINUTES};
+//! use khipu::{Glyph, SentimentIntensityAnalyzer, StreamBuilder};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let glyph: Glyph = unimplemented!();
+//! let mut params = FilterParams::new();
+//! params.track_phrase("rustlang").unwrap();
+//!
+//! let alerter = Alerter::new(vec![
+//!     Rule::mean_below(Window::Sliding(FIVE_MINUTES), -0.3, -0.1),
+//!     Rule::z_score(FIVE_MINUTES, 12, 3.0, 1.5),
+//!     Rule::volume_burst(FIVE_MINUTES, 12, 4.0, 2.0),
+//! ]);
+//!
+//! let stream = StreamBuilder::filter(glyph)
+//!     .params(&params)
+//!     .listen()
+//!     .unwrap()
+//!     .await
+//!     .unwrap();
+//! let mut alerts = stream.messages().alerts(
+//!     SentimentIntensityAnalyzer::new(),
+//!     TrackMatcher::from_params(&params),
+//!     alerter,
+//! );
+//! while let Some(alert) = alerts.try_next().await.unwrap() {
+//!     println!("{}", alert);
+//! }
+//! # }
+//! ```
+
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::matcher::{Matched, TrackMatcher};
+use crate::message::{Messages, StreamMessage, Tweet};
+use crate::sentiment::SentimentScores;
+use crate::window::{Aggregator, Change, Changes, Sample, Snapshot, Window, WindowStats};
+use crate::SentimentIntensityAnalyzer;
+
+/// The default `Rule::min_volume` of rules on the mean compound score.
+pub const MIN_VOLUME: u64 = 5;
+
+const EVIDENCE: usize = 5;
+
+/// A condition on the statistics of a window of a keyword.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Rule {
+    /// What the rule looks at.
+    pub kind: RuleKind,
+    /// The window the rule applies to.
+    pub window: Window,
+    /// The level at which the rule fires.
+    pub trigger: f64,
+    /// The level past which a fired rule is resolved.
+    pub clear: f64,
+    /// The number of samples a window must hold for the rule to be evaluated.
+    pub min_volume: u64,
+}
+
+/// The kinds of `Rule`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum RuleKind {
+    /// The mean compound score rises to `trigger` or above, and is resolved below `clear`.
+    MeanAbove,
+    /// The mean compound score falls to `trigger` or below, and is resolved above `clear`.
+    MeanBelow,
+    /// The z-score of the mean compound score against the given number of trailing periods
+    /// reaches `trigger` in absolute value, and is resolved below `clear`.
+    ZScore {
+        /// The number of trailing periods.
+        baseline: usize,
+    },
+    /// The volume reaches `trigger` times the mean volume of the given number of trailing
+    /// periods, and is resolved below `clear` times.
+    VolumeBurst {
+        /// The number of trailing periods.
+        baseline: usize,
+    },
+}
+
+/// A rule of an `Alerter` which fired or was resolved for a keyword.
+#[derive(Clone, Debug)]
+pub struct Alert {
+    /// The keyword.
+    pub keyword: String,
+    /// The rule.
+    pub rule: Rule,
+    /// Whether the rule fired or was resolved.
+    pub state: AlertState,
+    /// The value that reached the trigger or clear level of the rule.
+    pub kind: AlertKind,
+    /// Why the rule fired or was resolved.
+    pub evidence: Evidence,
+}
+
+/// Whether an `Alert` is raised or lifted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum AlertState {
+    /// The rule reached its trigger level.
+    Triggered,
+    /// The rule got back past its clear level.
+    Resolved,
+}
+
+/// The value that caused an `Alert`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum AlertKind {
+    /// The mean compound score crossed a level.
+    Threshold {
+        /// The mean compound score.
+        mean: f64,
+    },
+    /// The mean compound score moved away from the baseline.
+    ZScoreSpike {
+        /// The z-score of the mean compound score.
+        z: f64,
+    },
+    /// The volume moved away from the baseline.
+    VolumeBurst {
+        /// The volume divided by the mean volume of the baseline.
+        ratio: f64,
+    },
+}
+
+/// The evidence of an `Alert`.
+#[derive(Clone, Debug)]
+pub struct Evidence {
+    /// The statistics of the window the rule applies to.
+    pub stats: WindowStats,
+    /// The trailing periods the window was compared to, for `RuleKind::ZScore` and
+    /// `RuleKind::VolumeBurst`.
+    pub baseline: Option<Baseline>,
+    /// The latest Tweets of the keyword along with their scores, oldest first.
+    pub tweets: Vec<(Tweet, SentimentScores)>,
+}
+
+/// Statistics of the trailing periods of a tumbling window.
+///
+/// Periods without any Tweet count toward `mean_volume`, but have no compound score.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Baseline {
+    /// The number of periods.
+    pub periods: usize,
+    /// The number of periods without any Tweet.
+    pub empty: usize,
+    /// The mean of the mean compound scores of the periods with Tweets.
+    pub mean_compound: f64,
+    /// The standard deviation of the mean compound scores of the periods with Tweets.
+    pub compound_stddev: f64,
+    /// The mean volume of the periods.
+    pub mean_volume: f64,
+}
+
+/// Evaluates `Rule`s against rolling statistics of the scores of each keyword.
+pub struct Alerter {
+    rules: Vec<Rule>,
+    aggregator: Aggregator,
+    changes: Changes,
+    evidence: usize,
+    keywords: HashMap<String, KeywordState>,
+}
+
+/// A stream adapter returned by `Messages::alerts`.
+/// It yields the alerts raised by the Tweets of the underlying stream.
+pub struct AlertStream<'a, S> {
+    inner: S,
+    analyzer: SentimentIntensityAnalyzer<'a>,
+    matcher: TrackMatcher,
+    alerter: Alerter,
+    pending: VecDeque<Alert>,
+}
+
+struct KeywordState {
+    // Whether each rule fired.
+    active: Vec<bool>,
+    // The closed periods of each tumbling window.
+    periods: HashMap<Window, Periods>,
+    tweets: VecDeque<(Tweet, SentimentScores)>,
+}
+
+/// The closed periods of a tumbling window.
+#[derive(Default)]
+struct Periods {
+    // The end of the last recorded period.
+    end: Option<DateTime<Utc>>,
+    // The mean compound score and volume of the periods, oldest first.
+    periods: VecDeque<(f64, u64)>,
+}
+
+impl Rule {
+    /// A rule firing when the mean compound score of `window` rises to `trigger` or above.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `clear` is greater than `trigger`.
+    pub fn mean_above(window: Window, trigger: f64, clear: f64) -> Self {
+        assert!(clear <= trigger, "clear level above the trigger level");
+        Rule::new(RuleKind::MeanAbove, window, trigger, clear, MIN_VOLUME)
+    }
+
+    /// A rule firing when the mean compound score of `window` falls to `trigger` or below.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `clear` is less than `trigger`.
+    pub fn mean_below(window: Window, trigger: f64, clear: f64) -> Self {
+        assert!(clear >= trigger, "clear level below the trigger level");
+        Rule::new(RuleKind::MeanBelow, window, trigger, clear, MIN_VOLUME)
+    }
+
+    /// A rule firing when the z-score of the mean compound score of the current period of a
+    /// tumbling window of the given `length` reaches `trigger` in absolute value. The z-score is
+    /// computed against the mean compound scores of the last `baseline` periods, and the rule is
+    /// not evaluated until two periods have closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `baseline` is less than 2, or `clear` is not between 0 and `trigger`.
+    pub fn z_score(length: Duration, baseline: usize, trigger: f64, clear: f64) -> Self {
+        assert!(baseline >= 2, "baseline of less than two periods");
+        assert!(
+            0.0 <= clear && clear <= trigger,
+            "clear level not between 0 and the trigger level"
+        );
+        let kind = RuleKind::ZScore { baseline };
+        Rule::new(kind, Window::Tumbling(length), trigger, clear, MIN_VOLUME)
+    }
+
+    /// A rule firing when the volume of the current period of a tumbling window of the given
+    /// `length` reaches `trigger` times the mean volume of the last `baseline` periods. The rule
+    /// is not evaluated until a period has closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `baseline` is 0, or `clear` is greater than `trigger`.
+    pub fn volume_burst(length: Duration, baseline: usize, trigger: f64, clear: f64) -> Self {
+        assert!(baseline >= 1, "empty baseline");
+        assert!(clear <= trigger, "clear level above the trigger level");
+        let kind = RuleKind::VolumeBurst { baseline };
+        Rule::new(kind, Window::Tumbling(length), trigger, clear, 0)
+    }
+
+    /// Only evaluate the rule on windows holding at least `min_volume` samples. Defaults to
+    /// `MIN_VOLUME` for rules on the mean compound score, and to 0 for `Rule::volume_burst`.
+    pub fn min_volume(mut self, min_volume: u64) -> Self {
+        self.min_volume = min_volume;
+        self
+    }
+
+    fn new(kind: RuleKind, window: Window, trigger: f64, clear: f64, min_volume: u64) -> Self {
+        Rule {
+            kind,
+            window,
+            trigger,
+            clear,
+            min_volume,
+        }
+    }
+
+    fn baseline(&self) -> usize {
+        match self.kind {
+            RuleKind::ZScore { baseline } | RuleKind::VolumeBurst { baseline } => baseline,
+            RuleKind::MeanAbove | RuleKind::MeanBelow => 0,
+        }
+    }
+
+    fn triggers(&self, value: f64) -> bool {
+        match self.kind {
+            RuleKind::MeanBelow => value <= self.trigger,
+            _ => value >= self.trigger,
+        }
+    }
+
+    fn clears(&self, value: f64) -> bool {
+        match self.kind {
+            RuleKind::MeanBelow => value > self.clear,
+            _ => value < self.clear,
+        }
+    }
+
+    /// Returns the value of the rule for `stats`, or `None` if it cannot be evaluated.
+    fn evaluate(&self, stats: &WindowStats, baseline: Option<&Baseline>) -> Option<AlertKind> {
+        if stats.volume < self.min_volume {
+            return None;
+        }
+        match self.kind {
+            RuleKind::MeanAbove | RuleKind::MeanBelow => Some(AlertKind::Threshold {
+                mean: stats.mean.compound,
+            }),
+            RuleKind::ZScore { .. } => {
+                let baseline = baseline.filter(|b| b.periods - b.empty >= 2)?;
+                if baseline.compound_stddev < 1e-9 {
+                    return None;
+                }
+                let z = (stats.mean.compound - baseline.mean_compound) / baseline.compound_stddev;
+                Some(AlertKind::ZScoreSpike { z })
+            }
+            RuleKind::VolumeBurst { .. } => {
+                let baseline = baseline.filter(|b| b.mean_volume > 0.0)?;
+                let ratio = stats.volume as f64 / baseline.mean_volume;
+                Some(AlertKind::VolumeBurst { ratio })
+            }
+        }
+    }
+}
+
+impl AlertKind {
+    /// Returns the value compared to the levels of the rule.
+    pub fn value(&self) -> f64 {
+        match *self {
+            AlertKind::Threshold { mean } => mean,
+            AlertKind::ZScoreSpike { z } => z.abs(),
+            AlertKind::VolumeBurst { ratio } => ratio,
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            AlertState::Triggered => "triggered",
+            AlertState::Resolved => "resolved",
+        };
+        write!(f, "{} {} on {}: ", self.keyword, state, self.rule.window)?;
+        match self.kind {
+            AlertKind::Threshold { mean } => write!(f, "mean compound score {:.3}", mean)?,
+            AlertKind::ZScoreSpike { z } => write!(f, "z-score {:.2}", z)?,
+            AlertKind::VolumeBurst { ratio } => write!(f, "volume {:.1}x the baseline", ratio)?,
+        }
+        write!(f, " ({} Tweets)", self.evidence.stats.volume)
+    }
+}
+
+impl Baseline {
+    fn new(periods: &VecDeque<(f64, u64)>, count: usize) -> Option<Self> {
+        let skip = periods.len().saturating_sub(count);
+        let periods: Vec<_> = periods.iter().skip(skip).collect();
+        if periods.is_empty() {
+            return None;
+        }
+
+        let mean_volume = periods.iter().map(|p| p.1 as f64).sum::<f64>() / periods.len() as f64;
+        let scores: Vec<_> = periods.iter().filter(|p| p.1 > 0).map(|p| p.0).collect();
+        let (mean_compound, variance) = if scores.is_empty() {
+            (0.0, 0.0)
+        } else {
+            let n = scores.len() as f64;
+            let mean = scores.iter().sum::<f64>() / n;
+            let variance = scores.iter().map(|c| (c - mean) * (c - mean)).sum::<f64>() / n;
+            (mean, variance)
+        };
+
+        Some(Baseline {
+            periods: periods.len(),
+            empty: periods.len() - scores.len(),
+            mean_compound,
+            compound_stddev: variance.sqrt(),
+            mean_volume,
+        })
+    }
+}
+
+impl Periods {
+    /// Records the empty periods between the last recorded period and the window of `stats`.
+    /// The `Aggregator` does not publish a `Change::Closed` for those.
+    fn fill(&mut self, stats: &WindowStats, keep: usize) {
+        let end = match self.end {
+            Some(end) if end < stats.start => end,
+            _ => return,
+        };
+        let length = (stats.end - stats.start).num_milliseconds();
+        let empty = (stats.start - end).num_milliseconds() / length;
+        for _ in 0..cmp::min(empty as usize, keep) {
+            self.periods.push_back((0.0, 0));
+        }
+        self.truncate(keep);
+        self.end = Some(stats.start);
+    }
+
+    fn push(&mut self, stats: &WindowStats, keep: usize) {
+        self.periods.push_back((stats.mean.compound, stats.volume));
+        self.truncate(keep);
+        self.end = Some(stats.end);
+    }
+
+    fn truncate(&mut self, keep: usize) {
+        while self.periods.len() > keep {
+            self.periods.pop_front();
+        }
+    }
+}
+
+impl Alerter {
+    /// Creates an alerter evaluating the given rules. Its `Aggregator` has the windows of the
+    /// rules.
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let mut windows = Vec::new();
+        for rule in &rules {
+            if !windows.contains(&rule.window) {
+                windows.push(rule.window);
+            }
+        }
+        let aggregator = Aggregator::with_windows(&windows);
+        let changes = aggregator.changes();
+
+        Alerter {
+            rules,
+            aggregator,
+            changes,
+            evidence: EVIDENCE,
+            keywords: HashMap::new(),
+        }
+    }
+
+    /// Set the number of Tweets kept as evidence for each keyword. Defaults to 5.
+    pub fn evidence(&mut self, n: usize) -> &mut Self {
+        self.evidence = n;
+        self
+    }
+
+    /// Returns the rules.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Returns the aggregator holding the statistics the rules are evaluated against.
+    pub fn aggregator(&self) -> &Aggregator {
+        &self.aggregator
+    }
+
+    /// Returns the rules which fired for `keyword` and were not resolved yet.
+    pub fn active(&self, keyword: &str) -> Vec<&Rule> {
+        match self.keywords.get(keyword) {
+            Some(state) => self
+                .rules
+                .iter()
+                .zip(&state.active)
+                .filter(|&(_, &active)| active)
+                .map(|(rule, _)| rule)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Record a sample of a Tweet under each tracked phrase it matched, and return the alerts
+    /// it raised.
+    pub fn record(&mut self, matched: &Matched, sample: &Sample) -> Vec<Alert> {
+        let evidence = self.evidence;
+        for phrase in &matched.phrases {
+            let state = self.state(phrase);
+            state
+                .tweets
+                .push_back((matched.tweet.clone(), sample.scores));
+            while state.tweets.len() > evidence {
+                state.tweets.pop_front();
+            }
+            self.aggregator.record(phrase, sample);
+        }
+        self.drain()
+    }
+
+    /// Let time pass up to `now` for all the keywords, see `Aggregator::advance`, and return the
+    /// alerts raised by the periods which closed.
+    pub fn advance(&mut self, now: DateTime<Utc>) -> Vec<Alert> {
+        self.aggregator.advance(now);
+        self.drain()
+    }
+
+    /// Forget the statistics and the alerts of `keyword`, e.g. once it is no longer tracked.
+    pub fn remove(&mut self, keyword: &str) {
+        self.aggregator.remove(keyword);
+        self.keywords.remove(keyword);
+    }
+
+    /// Wrap a stream of messages, returning a stream of the alerts raised by its Tweets.
+    pub fn watch<'a, S>(
+        self,
+        stream: S,
+        analyzer: SentimentIntensityAnalyzer<'a>,
+        matcher: TrackMatcher,
+    ) -> AlertStream<'a, S>
+    where
+        S: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+    {
+        AlertStream {
+            inner: stream,
+            analyzer,
+            matcher,
+            alerter: self,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn state(&mut self, keyword: &str) -> &mut KeywordState {
+        let rules = self.rules.len();
+        self.keywords
+            .entry(keyword.to_owned())
+            .or_insert_with(|| KeywordState {
+                active: vec![false; rules],
+                periods: HashMap::new(),
+                tweets: VecDeque::new(),
+            })
+    }
+
+    fn drain(&mut self) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        while let Some(change) = self.changes.try_recv() {
+            match change {
+                Change::Updated(snapshot) => self.evaluate(&snapshot, false, &mut alerts),
+                Change::Closed(snapshot) => self.evaluate(&snapshot, true, &mut alerts),
+            }
+        }
+        alerts
+    }
+
+    fn evaluate(&mut self, snapshot: &Snapshot, closed: bool, alerts: &mut Vec<Alert>) {
+        let window = snapshot.window;
+        let tumbling = match window {
+            Window::Tumbling(_) => true,
+            Window::Sliding(_) => false,
+        };
+        let keep = self
+            .rules
+            .iter()
+            .filter(|rule| rule.window == window)
+            .map(Rule::baseline)
+            .max()
+            .unwrap_or(0);
+
+        let rules = &self.rules;
+        let state = match self.keywords.get_mut(&snapshot.keyword) {
+            Some(state) => state,
+            None => return,
+        };
+        let periods = state.periods.entry(window).or_insert_with(Periods::default);
+        if tumbling && keep > 0 {
+            periods.fill(&snapshot.stats, keep);
+        }
+
+        for (rule, active) in rules.iter().zip(&mut state.active) {
+            if rule.window != window {
+                continue;
+            }
+            let baseline = Baseline::new(&periods.periods, rule.baseline());
+            let kind = match rule.evaluate(&snapshot.stats, baseline.as_ref()) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            let alert_state = if !*active && rule.triggers(kind.value()) {
+                AlertState::Triggered
+            } else if *active && rule.clears(kind.value()) && (closed || !tumbling) {
+                AlertState::Resolved
+            } else {
+                continue;
+            };
+            *active = alert_state == AlertState::Triggered;
+
+            alerts.push(Alert {
+                keyword: snapshot.keyword.clone(),
+                rule: rule.clone(),
+                state: alert_state,
+                kind,
+                evidence: Evidence {
+                    stats: snapshot.stats.clone(),
+                    baseline,
+                    tweets: state.tweets.iter().cloned().collect(),
+                },
+            });
+        }
+
+        if closed && keep > 0 {
+            periods.push(&snapshot.stats, keep);
+        }
+    }
+}
+
+impl fmt::Debug for Alerter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alerter")
+            .field("rules", &self.rules)
+            .field("aggregator", &self.aggregator)
+            .field("evidence", &self.evidence)
+            .finish()
+    }
+}
+
+impl<'a, S> AlertStream<'a, S> {
+    /// Returns the alerter evaluating the rules.
+    pub fn alerter(&self) -> &Alerter {
+        &self.alerter
+    }
+
+    /// Returns the alerter evaluating the rules. Alerts returned by its methods are not yielded
+    /// by the stream, see `advance` to let time pass.
+    pub fn alerter_mut(&mut self) -> &mut Alerter {
+        &mut self.alerter
+    }
+
+    /// Let time pass up to `now` for all the keywords, see `Alerter::advance`. The alerts raised
+    /// by the periods which closed are yielded by the stream before those of the next Tweets.
+    pub fn advance(&mut self, now: DateTime<Utc>) {
+        let alerts = self.alerter.advance(now);
+        self.pending.extend(alerts);
+    }
+}
+
+impl<'a, S> Stream for AlertStream<'a, S>
+where
+    S: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+{
+    type Item = Result<Alert, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(alert) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(alert)));
+            }
+            if let StreamMessage::Tweet(tweet) = ready_some!(self.inner.poll_next_unpin(cx))? {
+                let this = &mut *self;
+                let matched = this.matcher.annotate(tweet);
+                let explanation = this
+                    .analyzer
+                    .polarity_scores_explained(matched.tweet.full_text());
+                let sample = Sample::from_explanation(matched.tweet.created_at, &explanation);
+                let alerts = this.alerter.record(&matched, &sample);
+                this.pending.extend(alerts);
+            }
+        }
+    }
+}
+
+impl Messages {
+    /// Turn the stream into one yielding the alerts `alerter` raises for the Tweets, scored by
+    /// `analyzer` and recorded under the tracked phrases they matched.
+    pub fn alerts<'a>(
+        self,
+        analyzer: SentimentIntensityAnalyzer<'a>,
+        matcher: TrackMatcher,
+        alerter: Alerter,
+    ) -> AlertStream<'a, Self> {
+        alerter.watch(self, analyzer, matcher)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::TimeZone;
+    use futures_executor::block_on_stream;
+    use futures_util::stream;
+
+    use crate::message::tweet;
+    use crate::window::MINUTE;
+
+    fn matched(id: u64, phrase: &str) -> Matched {
+        Matched {
+            tweet: tweet(id, ""),
+            phrases: vec![phrase.to_owned()],
+        }
+    }
+
+    fn sample(secs: i64, compound: f64) -> Sample {
+        let scores = SentimentScores {
+            compound,
+            ..Default::default()
+        };
+        Sample::new(Utc.timestamp(secs, 0), scores)
+    }
+
+    fn states(alerts: &[Alert]) -> Vec<AlertState> {
+        alerts.iter().map(|a| a.state).collect()
+    }
+
+    #[test]
+    fn threshold() {
+        let rule = Rule::mean_below(Window::Sliding(MINUTE), -0.5, -0.2).min_volume(1);
+        let mut alerter = Alerter::new(vec![rule]);
+
+        let alerts = alerter.record(&matched(1, "rust"), &sample(0, -0.6));
+        assert_eq!(states(&alerts), [AlertState::Triggered]);
+        assert_eq!(alerts[0].kind, AlertKind::Threshold { mean: -0.6 });
+        assert_eq!(alerter.active("rust").len(), 1);
+
+        // The mean is above the trigger level, but not above the clear level.
+        assert!(alerter
+            .record(&matched(2, "rust"), &sample(1, -0.2))
+            .is_empty());
+
+        let alerts = alerter.record(&matched(3, "rust"), &sample(2, 0.6));
+        assert_eq!(states(&alerts), [AlertState::Resolved]);
+        let tweets: Vec<_> = alerts[0].evidence.tweets.iter().map(|t| t.0.id).collect();
+        assert_eq!(tweets, [1, 2, 3]);
+        assert!(alerter.active("rust").is_empty());
+
+        assert!(alerter
+            .record(&matched(4, "rust"), &sample(3, -0.6))
+            .is_empty());
+        assert!(alerter
+            .record(&matched(5, "go"), &sample(3, 0.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn z_score() {
+        let rule = Rule::z_score(MINUTE, 3, 3.0, 1.0).min_volume(1);
+        let mut alerter = Alerter::new(vec![rule]);
+
+        for (i, &compound) in [0.1, 0.0, 0.1].iter().enumerate() {
+            let secs = i as i64 * 60;
+            assert!(alerter
+                .record(&matched(1, "rust"), &sample(secs, compound))
+                .is_empty());
+        }
+
+        let alerts = alerter.record(&matched(2, "rust"), &sample(180, -0.5));
+        assert_eq!(states(&alerts), [AlertState::Triggered]);
+        match alerts[0].kind {
+            AlertKind::ZScoreSpike { z } => assert!(z < -3.0),
+            ref k => panic!("expected ZScoreSpike, got {:?}", k),
+        }
+        let baseline = alerts[0].evidence.baseline.unwrap();
+        assert_eq!(baseline.periods, 3);
+        assert!((baseline.mean_compound - 0.2 / 3.0).abs() < 1e-9);
+
+        // Rules on tumbling windows are only resolved when a period closes.
+        assert!(alerter
+            .record(&matched(3, "rust"), &sample(240, 0.05))
+            .is_empty());
+        let alerts = alerter.advance(Utc.timestamp(300, 0));
+        assert_eq!(states(&alerts), [AlertState::Resolved]);
+        assert_eq!(alerts[0].evidence.baseline.unwrap().periods, 3);
+    }
+
+    #[test]
+    fn volume_burst() {
+        let rule = Rule::volume_burst(MINUTE, 2, 3.0, 1.5);
+        let mut alerter = Alerter::new(vec![rule]);
+        alerter.evidence(2);
+
+        let mut id = 0;
+        let mut record = |alerter: &mut Alerter, secs: i64| {
+            id += 1;
+            alerter.record(&matched(id, "rust"), &sample(secs, 0.0))
+        };
+
+        for &secs in &[0, 1, 60, 61, 120, 121, 122, 123, 124] {
+            assert!(record(&mut alerter, secs).is_empty());
+        }
+        let alerts = record(&mut alerter, 125);
+        assert_eq!(states(&alerts), [AlertState::Triggered]);
+        assert_eq!(alerts[0].kind, AlertKind::VolumeBurst { ratio: 3.0 });
+        assert_eq!(alerts[0].evidence.stats.volume, 6);
+        assert_eq!(alerts[0].evidence.tweets.len(), 2);
+
+        let alerts = record(&mut alerter, 180);
+        assert!(alerts.is_empty());
+        let alerts = alerter.advance(Utc.timestamp(240, 0));
+        assert_eq!(states(&alerts), [AlertState::Resolved]);
+    }
+
+    #[test]
+    fn stream_advance() {
+        let rule = Rule::volume_burst(MINUTE, 2, 3.0, 1.5);
+        let mut alerter = Alerter::new(vec![rule]);
+        let secs = [0, 1, 60, 61, 120, 121, 122, 123, 124, 125, 180];
+        for (id, &secs) in secs.iter().enumerate() {
+            alerter.record(&matched(id as u64, "rust"), &sample(secs, 0.0));
+        }
+        assert_eq!(alerter.active("rust").len(), 1);
+
+        let mut stream = alerter.watch(
+            stream::empty(),
+            SentimentIntensityAnalyzer::new(),
+            TrackMatcher::new(&["rust"]),
+        );
+        stream.advance(Utc.timestamp(240, 0));
+        let alerts: Vec<_> = block_on_stream(stream).map(Result::unwrap).collect();
+        assert_eq!(states(&alerts), [AlertState::Resolved]);
+    }
+
+    #[test]
+    fn empty_periods() {
+        let rule = Rule::volume_burst(MINUTE, 4, 3.0, 1.5);
+        let mut alerter = Alerter::new(vec![rule]);
+
+        for secs in 0..6 {
+            assert!(alerter
+                .record(&matched(1, "rust"), &sample(secs, 0.0))
+                .is_empty());
+        }
+        // The next three minutes have no Tweets. Only the first minute is closed.
+        assert!(alerter.advance(Utc.timestamp(150, 0)).is_empty());
+        for secs in 240..244 {
+            assert!(alerter
+                .record(&matched(2, "rust"), &sample(secs, 0.0))
+                .is_empty());
+        }
+
+        let alerts = alerter.record(&matched(3, "rust"), &sample(244, 0.0));
+        assert_eq!(states(&alerts), [AlertState::Triggered]);
+        let baseline = alerts[0].evidence.baseline.unwrap();
+        assert_eq!(baseline.periods, 4);
+        assert_eq!(baseline.empty, 3);
+        assert_eq!(baseline.mean_volume, 1.5);
+        assert_eq!(alerts[0].kind, AlertKind::VolumeBurst { ratio: 5.0 / 1.5 });
+    }
+}
@@ -0,0 +1,703 @@
+//! Authentication schemes accepted by the Twitter API.
+//!
+//! `StreamBuilder`, the `v2` module and the REST helpers of this crate are generic over
+//! `Authenticate`, which is implemented by:
+//!
+//! * `Glyph`, an OAuth 1.0a client and access token pair acting on behalf of a user,
+//! * `Bearer`, an OAuth 2.0 bearer token, either app-only or acting on behalf of a user,
+//! * `UserToken`, the OAuth 2.0 user access token returned by `Pkce::exchange`.
+//!
+//! A `Glyph` can be obtained through the three-legged OAuth 1.0a flow, starting with
+//! `RequestToken::obtain`. An app-only `Bearer` can be obtained from the consumer key and secret
+//! of an app with `Bearer::app_only`, which performs the client credentials exchange. User tokens
+//! are obtained through the Authorization Code flow with PKCE, starting with `Pkce::new`.
+//!
+//! Note that the v1.1 Streaming API only accepts OAuth 1.0a, and the v2 filtered stream only
+//! accepts app-only bearer tokens.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use khipu::auth::Pkce;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), khipu::Error> {
+//! let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new().unwrap());
+//!
+//! let pkce = Pkce::new("client_id", "https://example.com/callback");
+//! println!("Open {}", pkce.authorize_url(&["tweet.read", "users.read"], "state"));
+//!
+//! // Once redirected to `https://example.com/callback?state=state&code=...`:
+//! let token = pkce.exchange(&client, "code").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use bytes::Bytes;
+use futures_util::TryStreamExt;
+use hyper::body::Payload;
+use hyper::client::connect::Connect;
+use hyper::client::Client;
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Chunk, Request};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::form_urlencoded;
+
+use crate::error::Error;
+use crate::filters::Uri;
+use crate::Credentials;
+use crate::Glyph;
+
+const REQUEST_TOKEN_URI: &str = "https://api.twitter.com/oauth/request_token";
+const OAUTH1_AUTHORIZE_URI: &str = "https://api.twitter.com/oauth/authorize";
+const OAUTH1_AUTHENTICATE_URI: &str = "https://api.twitter.com/oauth/authenticate";
+const ACCESS_TOKEN_URI: &str = "https://api.twitter.com/oauth/access_token";
+const APP_ONLY_TOKEN_URI: &str = "https://api.twitter.com/oauth2/token";
+const AUTHORIZE_URI: &str = "https://twitter.com/i/oauth2/authorize";
+const TOKEN_URI: &str = "https://api.twitter.com/2/oauth2/token";
+
+/// Credentials that can authorize requests to the Twitter API.
+pub trait Authenticate {
+    /// Serializes `params` into the query of `uri` and returns the resulting URI along with the
+    /// value of the `Authorization` header for a request to it.
+    fn authorize<U: Display, P: oauth::Authorize>(
+        &self,
+        method: &str,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request;
+
+    /// Serializes `params` as an `application/x-www-form-urlencoded` body and returns it along
+    /// with the value of the `Authorization` header for a `POST` request to `uri`.
+    fn authorize_form<U: Display, P: oauth::Authorize>(&self, uri: U, params: &P)
+        -> oauth::Request;
+}
+
+/// The callback of the PIN-based OAuth 1.0a flow, for apps which cannot receive redirects.
+/// Twitter displays a PIN to the user instead, to be passed to `RequestToken::access_token`.
+pub const OUT_OF_BAND: &str = "oob";
+
+/// A temporary token of the three-legged OAuth 1.0a flow, to be exchanged for a `Glyph` once
+/// the user authorized the app.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// Like that of `Glyph`, the `Debug` representation of a `RequestToken` does not show the
+/// secrets.
+///
+/// [1]: https://developer.twitter.com/en/docs/authentication/oauth-1-0a/obtaining-user-access-tokens
+#[derive(Clone)]
+pub struct RequestToken {
+    pub client: Credentials,
+    pub token: Credentials,
+}
+
+/// The result of `RequestToken::access_token`.
+#[derive(Clone, Debug)]
+pub struct AccessToken {
+    /// The credentials of the app and of the user who authorized it.
+    pub glyph: Glyph,
+    /// The numeric ID of the user.
+    pub user_id: u64,
+    /// The screen name of the user.
+    pub screen_name: String,
+}
+
+/// An OAuth 2.0 bearer token.
+///
+/// Like that of `Glyph`, the `Debug` representation of a `Bearer` does not show the token.
+#[derive(Clone)]
+pub struct Bearer<T = String> {
+    pub token: T,
+}
+
+/// The Authorization Code flow with PKCE, which obtains a `UserToken` on behalf of a user.
+///
+/// See the [Twitter Developer Documentation][1] for more information.
+///
+/// The `Debug` representation of a `Pkce` does not show the code verifier.
+///
+/// [1]: https://developer.twitter.com/en/docs/authentication/oauth-2-0/authorization-code
+#[derive(Clone)]
+pub struct Pkce {
+    client_id: String,
+    redirect_uri: String,
+    verifier: String,
+}
+
+/// An OAuth 2.0 access token acting on behalf of a user.
+///
+/// The `Debug` representation of a `UserToken` does not show the tokens.
+#[derive(Clone, Deserialize)]
+pub struct UserToken {
+    pub access_token: String,
+    /// A token to obtain a new `UserToken` with once this one expires. Only issued if the
+    /// `offline.access` scope was requested.
+    pub refresh_token: Option<String>,
+    /// The lifetime of `access_token`, in seconds.
+    pub expires_in: Option<u64>,
+    /// The space-separated scopes granted by the user.
+    #[serde(default)]
+    pub scope: String,
+}
+
+#[derive(Deserialize)]
+struct AppOnlyToken {
+    token_type: String,
+    access_token: String,
+}
+
+impl<C: Borrow<str>, A: Borrow<str>> Authenticate for Glyph<C, A> {
+    fn authorize<U: Display, P: oauth::Authorize>(
+        &self,
+        method: &str,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        let mut oauth = oauth::StreamBuilder::new(self.client.as_ref(), oauth::HmacSha1);
+        oauth.glyph(self.glyph.as_ref());
+        oauth.build(method, uri, params)
+    }
+
+    fn authorize_form<U: Display, P: oauth::Authorize>(
+        &self,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        let mut oauth = oauth::StreamBuilder::new(self.client.as_ref(), oauth::HmacSha1);
+        oauth.glyph(self.glyph.as_ref());
+        oauth.post_form(uri, params)
+    }
+}
+
+impl RequestToken {
+    /// Obtain a request token for the app identified by `client`.
+    ///
+    /// `callback` is the URL the user is redirected to once they authorized the app, which must
+    /// be registered with the app, or `OUT_OF_BAND` for the PIN-based flow.
+    pub async fn obtain<Conn, B>(
+        client: &Client<Conn, B>,
+        consumer: Credentials<&str>,
+        callback: &str,
+    ) -> Result<Self, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let mut oauth = oauth::StreamBuilder::new(consumer, oauth::HmacSha1);
+        oauth.callback(callback);
+        let oauth::Request { authorization, .. } = oauth.post_form(REQUEST_TOKEN_URI, &());
+
+        let mut res = post_oauth1(client, REQUEST_TOKEN_URI, authorization).await?;
+        if res.get("oauth_callback_confirmed").map(String::as_str) != Some("true") {
+            return Err(Error::MissingValue("oauth_callback_confirmed"));
+        }
+        let token = Credentials::new(
+            take(&mut res, "oauth_token")?,
+            take(&mut res, "oauth_token_secret")?,
+        );
+
+        Ok(RequestToken {
+            client: Credentials::new(consumer.identifier().into(), consumer.secret().into()),
+            token,
+        })
+    }
+
+    /// Returns the URL to send the user to in order to authorize the app.
+    pub fn authorize_url(&self) -> String {
+        format!(
+            "{}?oauth_token={}",
+            OAUTH1_AUTHORIZE_URI,
+            self.token.identifier()
+        )
+    }
+
+    /// Same as `authorize_url`, except that users who already authorized the app are
+    /// redirected right away ("Sign in with Twitter").
+    pub fn authenticate_url(&self) -> String {
+        format!(
+            "{}?oauth_token={}",
+            OAUTH1_AUTHENTICATE_URI,
+            self.token.identifier()
+        )
+    }
+
+    /// Extracts the verifier from the URI the user was redirected to, checking that it was
+    /// issued for this request token.
+    pub fn verifier_from_callback(&self, uri: &Uri) -> Result<String, Error> {
+        let mut params: HashMap<_, _> =
+            form_urlencoded::parse(uri.query().unwrap_or("").as_bytes())
+                .into_owned()
+                .collect();
+        if params.contains_key("denied") {
+            return Err(Error::Custom("the user denied the authorization".into()));
+        }
+        if take(&mut params, "oauth_token")? != self.token.identifier() {
+            return Err(Error::Custom("mismatched oauth_token in callback".into()));
+        }
+        take(&mut params, "oauth_verifier")
+    }
+
+    /// Exchange the request token for an access token, given the PIN displayed to the user or
+    /// the verifier passed to the callback URL.
+    pub async fn access_token<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        verifier: &str,
+    ) -> Result<AccessToken, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let mut oauth = oauth::StreamBuilder::new(self.client.as_ref(), oauth::HmacSha1);
+        oauth.glyph(self.token.as_ref()).verifier(verifier.trim());
+        let oauth::Request { authorization, .. } = oauth.post_form(ACCESS_TOKEN_URI, &());
+
+        let mut res = post_oauth1(client, ACCESS_TOKEN_URI, authorization).await?;
+        let glyph = Glyph::from_credentials(
+            self.client.clone(),
+            Credentials::new(
+                take(&mut res, "oauth_token")?,
+                take(&mut res, "oauth_token_secret")?,
+            ),
+        );
+        let user_id = take(&mut res, "user_id")?.parse()?;
+        let screen_name = take(&mut res, "screen_name")?;
+
+        Ok(AccessToken {
+            glyph,
+            user_id,
+            screen_name,
+        })
+    }
+}
+
+impl fmt::Debug for RequestToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestToken")
+            .field("consumer_key", &self.client.identifier())
+            .field("consumer_secret", &"<redacted>")
+            .field("token", &self.token.identifier())
+            .field("token_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+impl<T: Borrow<str>> Bearer<T> {
+    pub fn new(token: T) -> Self {
+        Bearer { token }
+    }
+
+    /// Borrow the token from `self` and make a new `Bearer` with it.
+    pub fn as_ref(&self) -> Bearer<&str> {
+        Bearer::new(self.token.borrow())
+    }
+
+    fn header(&self) -> String {
+        format!("Bearer {}", self.token.borrow())
+    }
+}
+
+impl Bearer {
+    /// Obtain an app-only bearer token from the consumer key and secret of an app.
+    ///
+    /// See the [Twitter Developer Documentation][1] for more information.
+    ///
+    /// [1]: https://developer.twitter.com/en/docs/authentication/api-reference/token
+    pub async fn app_only<Conn, B>(
+        client: &Client<Conn, B>,
+        consumer_key: &str,
+        consumer_secret: &str,
+    ) -> Result<Self, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let credentials = format!(
+            "{}:{}",
+            form_urlencoded::byte_serialize(consumer_key.as_bytes()).collect::<String>(),
+            form_urlencoded::byte_serialize(consumer_secret.as_bytes()).collect::<String>(),
+        );
+        let authorization = format!("Basic {}", base64::encode(&credentials));
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "client_credentials")
+            .finish();
+
+        let uri = Uri::from_static(APP_ONLY_TOKEN_URI);
+        let token: AppOnlyToken = post_form(client, uri, authorization, body).await?;
+        if !token.token_type.eq_ignore_ascii_case("bearer") {
+            return Err(Error::Custom(
+                format!("unexpected token type: {}", token.token_type).into(),
+            ));
+        }
+
+        Ok(Bearer::new(token.access_token))
+    }
+}
+
+impl<T> fmt::Debug for Bearer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bearer")
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
+
+// The parameters are serialized by an OAuth 1.0a builder with empty credentials, whose
+// `Authorization` header is discarded.
+impl<T: Borrow<str>> Authenticate for Bearer<T> {
+    fn authorize<U: Display, P: oauth::Authorize>(
+        &self,
+        method: &str,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        let oauth::Request { data, .. } =
+            oauth::StreamBuilder::new(oauth::Credentials::new("", ""), oauth::Plaintext)
+                .build(method, uri, params);
+        oauth::Request {
+            authorization: self.header(),
+            data,
+        }
+    }
+
+    fn authorize_form<U: Display, P: oauth::Authorize>(
+        &self,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        let oauth::Request { data, .. } =
+            oauth::StreamBuilder::new(oauth::Credentials::new("", ""), oauth::Plaintext)
+                .post_form(uri, params);
+        oauth::Request {
+            authorization: self.header(),
+            data,
+        }
+    }
+}
+
+impl Pkce {
+    /// Start the flow for the app identified by `client_id`. The user is redirected to
+    /// `redirect_uri`, which must be registered with the app, once they authorized it.
+    pub fn new(client_id: impl Into<String>, redirect_uri: impl Into<String>) -> Self {
+        let verifier = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(64)
+            .collect();
+        Pkce {
+            client_id: client_id.into(),
+            redirect_uri: redirect_uri.into(),
+            verifier,
+        }
+    }
+
+    /// Returns the code verifier, to be kept until `exchange` is called.
+    pub fn code_verifier(&self) -> &str {
+        &self.verifier
+    }
+
+    /// Returns the URL to send the user to in order to authorize the app.
+    ///
+    /// `state` is passed back along with the authorization code and should be checked against
+    /// the value given here.
+    pub fn authorize_url(&self, scopes: &[&str], state: &str) -> String {
+        let challenge = base64::encode_config(
+            &Sha256::digest(self.verifier.as_bytes()),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &scopes.join(" "))
+            .append_pair("state", state)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256")
+            .finish();
+        format!("{}?{}", AUTHORIZE_URI, query)
+    }
+
+    /// Exchange the authorization code passed to the redirect URI for a `UserToken`.
+    pub async fn exchange<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        code: &str,
+    ) -> Result<UserToken, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("code_verifier", &self.verifier)
+            .finish();
+        post_form(client, Uri::from_static(TOKEN_URI), None, body).await
+    }
+}
+
+impl fmt::Debug for Pkce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pkce")
+            .field("client_id", &self.client_id)
+            .field("redirect_uri", &self.redirect_uri)
+            .field("verifier", &"<redacted>")
+            .finish()
+    }
+}
+
+impl UserToken {
+    /// Obtain a new `UserToken` with `refresh_token`, on behalf of the app identified by
+    /// `client_id`.
+    pub async fn refresh<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        client_id: &str,
+    ) -> Result<UserToken, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let refresh_token = self
+            .refresh_token
+            .as_ref()
+            .ok_or(Error::MissingValue("refresh_token"))?;
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "refresh_token")
+            .append_pair("refresh_token", refresh_token)
+            .append_pair("client_id", client_id)
+            .finish();
+        post_form(client, Uri::from_static(TOKEN_URI), None, body).await
+    }
+
+    /// Borrow the access token from `self` and make a `Bearer` with it.
+    pub fn bearer(&self) -> Bearer<&str> {
+        Bearer::new(&self.access_token)
+    }
+}
+
+impl fmt::Debug for UserToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserToken")
+            .field("access_token", &"<redacted>")
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("expires_in", &self.expires_in)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+impl Authenticate for UserToken {
+    fn authorize<U: Display, P: oauth::Authorize>(
+        &self,
+        method: &str,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        self.bearer().authorize(method, uri, params)
+    }
+
+    fn authorize_form<U: Display, P: oauth::Authorize>(
+        &self,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        self.bearer().authorize_form(uri, params)
+    }
+}
+
+impl<'a, T: Authenticate> Authenticate for &'a T {
+    fn authorize<U: Display, P: oauth::Authorize>(
+        &self,
+        method: &str,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        (**self).authorize(method, uri, params)
+    }
+
+    fn authorize_form<U: Display, P: oauth::Authorize>(
+        &self,
+        uri: U,
+        params: &P,
+    ) -> oauth::Request {
+        (**self).authorize_form(uri, params)
+    }
+}
+
+/// Sends a form to a token endpoint and parses the JSON response.
+pub(crate) async fn post_form<Conn, B, R>(
+    client: &Client<Conn, B>,
+    uri: Uri,
+    authorization: impl Into<Option<String>>,
+    body: String,
+) -> Result<R, Error>
+where
+    Conn: Connect + Sync + 'static,
+    Conn::Transport: 'static,
+    Conn::Future: 'static,
+    B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+    B::Data: Send + Unpin,
+    R: DeserializeOwned,
+{
+    let body = send_form(client, uri, authorization.into(), body).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Sends a form to an OAuth 1.0a endpoint and parses the form-encoded response.
+async fn post_oauth1<Conn, B>(
+    client: &Client<Conn, B>,
+    uri: &'static str,
+    authorization: String,
+) -> Result<HashMap<String, String>, Error>
+where
+    Conn: Connect + Sync + 'static,
+    Conn::Transport: 'static,
+    Conn::Future: 'static,
+    B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+    B::Data: Send + Unpin,
+{
+    let uri = Uri::from_static(uri);
+    let body = send_form(client, uri, Some(authorization), String::new()).await?;
+    Ok(form_urlencoded::parse(&body).into_owned().collect())
+}
+
+async fn send_form<Conn, B>(
+    client: &Client<Conn, B>,
+    uri: Uri,
+    authorization: Option<String>,
+    body: String,
+) -> Result<Chunk, Error>
+where
+    Conn: Connect + Sync + 'static,
+    Conn::Transport: 'static,
+    Conn::Future: 'static,
+    B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+    B::Data: Send + Unpin,
+{
+    let mut req = Request::post(uri);
+    req.header(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+    if let Some(authorization) = authorization {
+        req.header(AUTHORIZATION, Bytes::from(authorization));
+    }
+    let req = req.body(body.into_bytes().into()).unwrap();
+
+    let res = client.request(req).await?;
+    if !res.status().is_success() {
+        return Err(Error::read_response(res).await);
+    }
+    Ok(res.into_body().try_concat().await?)
+}
+
+fn take(params: &mut HashMap<String, String>, name: &'static str) -> Result<String, Error> {
+    params.remove(name).ok_or(Error::MissingValue(name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(oauth::Authorize)]
+    struct Params {
+        track: &'static str,
+    }
+
+    #[test]
+    fn pkce_challenge() {
+        // The example of RFC 7636, Appendix B.
+        let pkce = Pkce {
+            client_id: "client_id".into(),
+            redirect_uri: "https://example.com/callback".into(),
+            verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".into(),
+        };
+        let url = pkce.authorize_url(&["tweet.read", "users.read"], "state");
+        let (base, query) = url.split_at(url.find('?').unwrap());
+        assert_eq!(base, AUTHORIZE_URI);
+
+        let params: HashMap<_, _> = form_urlencoded::parse(query[1..].as_bytes())
+            .into_owned()
+            .collect();
+        assert_eq!(
+            params["code_challenge"],
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(params["scope"], "tweet.read users.read");
+        assert_eq!(params["redirect_uri"], "https://example.com/callback");
+        assert_eq!(params["state"], "state");
+
+        assert!(!format!("{:?}", pkce).contains(pkce.code_verifier()));
+    }
+
+    #[test]
+    fn bearer() {
+        let bearer = Bearer::new("AAAA");
+        let params = Params { track: "rust lang" };
+
+        let oauth::Request {
+            authorization,
+            data,
+        } = bearer.authorize("GET", "https://example.com/stream", &params);
+        assert_eq!(authorization, "Bearer AAAA");
+        assert_eq!(data, "https://example.com/stream?track=rust%20lang");
+
+        let oauth::Request {
+            authorization,
+            data,
+        } = bearer.authorize_form("https://example.com/stream", &params);
+        assert_eq!(authorization, "Bearer AAAA");
+        assert_eq!(data, "track=rust%20lang");
+    }
+
+    #[test]
+    fn debug() {
+        let token = RequestToken {
+            client: Credentials::new("consumer_key".into(), "consumer_secret".into()),
+            token: Credentials::new("request_token".into(), "request_secret".into()),
+        };
+        let debug = format!("{:?}", token);
+        assert!(debug.contains("request_token"));
+        assert!(!debug.contains("consumer_secret\""));
+        assert!(!debug.contains("request_secret"));
+
+        let token: UserToken = serde_json::from_str(
+            r#"{"access_token":"access","refresh_token":"refresh","expires_in":7200}"#,
+        )
+        .unwrap();
+        let debug = format!("{:?}", token);
+        assert!(!debug.contains("access\""));
+        assert!(!debug.contains("refresh\""));
+        assert!(debug.contains("7200"));
+    }
+}
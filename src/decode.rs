@@ -0,0 +1,210 @@
+//! Decoding of compressed response bodies.
+//!
+//! Streams are requested with an `Accept-Encoding` of `ACCEPTED_ENCODINGS`, and the body of the
+//! response is decoded according to its `Content-Encoding` headers, which are matched
+//! case-insensitively. `gzip` (or `x-gzip`), `deflate`, `br` and `identity` are supported. A body
+//! encoded with an unknown coding, or with more than one coding, is refused with
+//! `Error::UnsupportedEncoding` rather than being passed on undecoded.
+
+use std::io;
+use std::marker::Unpin;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_compression::stream::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+use bytes::Bytes;
+use futures_core::{Stream, TryStream};
+use futures_util::{ready, StreamExt, TryStreamExt};
+use hyper::header::{HeaderMap, CONTENT_ENCODING};
+use hyper::Chunk;
+
+use crate::error::Error;
+
+/// The value of the `Accept-Encoding` header sent to the server.
+pub const ACCEPTED_ENCODINGS: &str = "gzip, deflate, br";
+
+/// A content coding of a response body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+/// A stream decoding a response body according to its `Encoding`.
+pub struct Decoder<S: TryStream + Unpin>(Inner<S>)
+where
+    S::Ok: Into<Bytes>,
+    S::Error: Unpin;
+
+enum Inner<S: TryStream + Unpin>
+where
+    S::Ok: Into<Bytes>,
+    S::Error: Unpin,
+{
+    Identity(S),
+    Gzip(GzipDecoder<Adapter<S, S::Error>>),
+    Deflate(ZlibDecoder<Adapter<S, S::Error>>),
+    Brotli(BrotliDecoder<Adapter<S, S::Error>>),
+}
+
+/// Feeds the body to the decoders of `async_compression`, which only accept `io::Error`s.
+/// The original error is kept aside so that it can be returned in place of the `io::Error`.
+struct Adapter<S, E> {
+    inner: S,
+    error: Option<E>,
+}
+
+impl Encoding {
+    /// Parses a content coding token, ignoring case.
+    pub fn from_token(token: &str) -> Option<Self> {
+        let token = token.trim();
+        if token.eq_ignore_ascii_case("identity") {
+            Some(Encoding::Identity)
+        } else if token.eq_ignore_ascii_case("gzip") || token.eq_ignore_ascii_case("x-gzip") {
+            Some(Encoding::Gzip)
+        } else if token.eq_ignore_ascii_case("deflate") {
+            Some(Encoding::Deflate)
+        } else if token.eq_ignore_ascii_case("br") {
+            Some(Encoding::Brotli)
+        } else {
+            None
+        }
+    }
+
+    /// Determines the encoding of a response body from its `Content-Encoding` headers.
+    ///
+    /// Unknown codings, and more than one coding other than `identity`, are reported as
+    /// `Error::UnsupportedEncoding`.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, Error> {
+        let mut encoding = Encoding::Identity;
+
+        for value in headers.get_all(CONTENT_ENCODING) {
+            let value = value.to_str()?;
+            for token in value.split(',').filter(|t| !t.trim().is_empty()) {
+                match (encoding, Encoding::from_token(token)) {
+                    (_, Some(Encoding::Identity)) => (),
+                    (Encoding::Identity, Some(e)) => encoding = e,
+                    _ => {
+                        return Err(Error::UnsupportedEncoding(value.to_owned()));
+                    }
+                }
+            }
+        }
+
+        Ok(encoding)
+    }
+}
+
+impl<S: TryStream<Ok = Chunk> + Unpin> Decoder<S>
+where
+    S::Error: Unpin,
+{
+    pub fn new(s: S, encoding: Encoding) -> Self {
+        Decoder(match encoding {
+            Encoding::Identity => Inner::Identity(s),
+            Encoding::Gzip => Inner::Gzip(GzipDecoder::new(Adapter::new(s))),
+            Encoding::Deflate => Inner::Deflate(ZlibDecoder::new(Adapter::new(s))),
+            Encoding::Brotli => Inner::Brotli(BrotliDecoder::new(Adapter::new(s))),
+        })
+    }
+}
+
+impl<S, E> Adapter<S, E> {
+    fn new(inner: S) -> Self {
+        Adapter { inner, error: None }
+    }
+}
+
+macro_rules! poll_decoder {
+    ($decoder:expr, $cx:expr) => {
+        match ready!($decoder.poll_next_unpin($cx)) {
+            Some(Ok(bytes)) => Poll::Ready(Some(Ok(bytes.into()))),
+            Some(Err(e)) => {
+                let error = $decoder.get_mut().error.take();
+                Poll::Ready(Some(Err(error.unwrap_or(Error::Decode(e)))))
+            }
+            None => Poll::Ready(None),
+        }
+    };
+}
+
+impl<S: TryStream<Ok = Chunk, Error = Error> + Unpin> Stream for Decoder<S> {
+    type Item = Result<Chunk, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.0 {
+            Inner::Identity(ref mut s) => s.try_poll_next_unpin(cx),
+            Inner::Gzip(ref mut d) => poll_decoder!(d, cx),
+            Inner::Deflate(ref mut d) => poll_decoder!(d, cx),
+            Inner::Brotli(ref mut d) => poll_decoder!(d, cx),
+        }
+    }
+}
+
+impl<S: TryStream + Unpin> Stream for Adapter<S, S::Error>
+where
+    S::Ok: Into<Bytes>,
+    S::Error: Unpin,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match ready!(self.inner.try_poll_next_unpin(cx)) {
+            Some(Ok(chunk)) => Poll::Ready(Some(Ok(chunk.into()))),
+            Some(Err(e)) => {
+                self.error = Some(e);
+                Poll::Ready(Some(Err(io::Error::from_raw_os_error(0))))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use hyper::header::HeaderValue;
+
+    fn from_headers(values: &[&'static str]) -> Result<Encoding, Error> {
+        let mut headers = HeaderMap::new();
+        for &value in values {
+            headers.append(CONTENT_ENCODING, HeaderValue::from_static(value));
+        }
+        Encoding::from_headers(&headers)
+    }
+
+    #[test]
+    fn encodings() {
+        assert_eq!(from_headers(&[]).unwrap(), Encoding::Identity);
+        assert_eq!(from_headers(&["identity"]).unwrap(), Encoding::Identity);
+        assert_eq!(from_headers(&["gzip"]).unwrap(), Encoding::Gzip);
+        assert_eq!(from_headers(&["GZip"]).unwrap(), Encoding::Gzip);
+        assert_eq!(from_headers(&["x-gzip"]).unwrap(), Encoding::Gzip);
+        assert_eq!(from_headers(&["X-GZIP"]).unwrap(), Encoding::Gzip);
+        assert_eq!(from_headers(&["Deflate"]).unwrap(), Encoding::Deflate);
+        assert_eq!(from_headers(&["BR"]).unwrap(), Encoding::Brotli);
+
+        // `identity` and empty elements do not count as a coding.
+        assert_eq!(from_headers(&["identity, gzip"]).unwrap(), Encoding::Gzip);
+        assert_eq!(from_headers(&["br,"]).unwrap(), Encoding::Brotli);
+        assert_eq!(from_headers(&["identity", "br"]).unwrap(), Encoding::Brotli);
+    }
+
+    #[test]
+    fn unsupported() {
+        for values in &[
+            &["compress"][..],
+            &["gzip, br"],
+            &["gzip, gzip"],
+            &["deflate", "gzip"],
+        ] {
+            match from_headers(values) {
+                Err(Error::UnsupportedEncoding(_)) => (),
+                r => panic!("expected UnsupportedEncoding for {:?}, got {:?}", values, r),
+            }
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Sentence-level and document-level sentiment.
+//!
+//! `SentimentIntensityAnalyzer::polarity_scores` scores its input as one flat sequence of
+//! glyphs, which muddies the score of long texts such as Tweet threads or articles: a single
+//! "but" rescales the whole text, and opposite sentences cancel each other out.
+//! `SentimentIntensityAnalyzer::document_scores` instead splits the text into sentences, scores
+//! each one independently, and aggregates the results with a selectable `Aggregation`.
+//!
+//! ### Example
+//!
+//! ```rust
+//! use khipu::document::Aggregation;
+//! use khipu::SentimentIntensityAnalyzer;
+//!
+//! let text = "The food was great! But the service was terrible.";
+//! let analyzer = SentimentIntensityAnalyzer::new();
+//! let document = analyzer.document_scores(text, Aggregation::LengthWeighted);
+//!
+//! assert_eq!(document.sentences.len(), 2);
+//! assert_eq!(document.sentences[0].substr(text), "The food was great!");
+//! assert!(document.sentences[0].scores.compound > 0.0);
+//! assert!(document.sentences[1].scores.compound < 0.0);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::sentiment::SentimentScores;
+use crate::{ParsedText, SentimentIntensityAnalyzer};
+
+/// How the scores of the sentences of a document are combined into the score of the document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Aggregation {
+    /// The average of the scores of the sentences.
+    Mean,
+    /// The average of the scores of the sentences, weighted by their number of glyphs.
+    LengthWeighted,
+    /// The scores of the sentence with the largest absolute compound score.
+    MaxMagnitude,
+}
+
+/// The scores of a single sentence of a document.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SentenceSentiment {
+    /// The byte offsets of the sentence in the document, excluding surrounding whitespace.
+    pub range: (usize, usize),
+    /// The number of glyphs in the sentence.
+    pub glyphs: usize,
+    /// The scores of the sentence.
+    pub scores: SentimentScores,
+}
+
+/// The scores of a document, returned by `SentimentIntensityAnalyzer::document_scores`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DocumentSentiment {
+    /// The scores of each sentence, in order of appearance.
+    pub sentences: Vec<SentenceSentiment>,
+    /// The aggregation used to compute `total`.
+    pub aggregation: Aggregation,
+    /// The aggregated scores of the document.
+    pub total: SentimentScores,
+}
+
+impl SentenceSentiment {
+    /// Returns the text of the sentence from the given document.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the byte offsets of the sentence do not match codepoint
+    /// boundaries in the given text. This can happen if the text is not the document the
+    /// sentence was split from.
+    pub fn substr<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.range.0..self.range.1]
+    }
+}
+
+impl<'a> SentimentIntensityAnalyzer<'a> {
+    /// Splits `text` into sentences with `split_sentences`, scores each of them with
+    /// `polarity_scores` and aggregates the scores with `aggregation`.
+    ///
+    /// Sentences without any glyph, e.g. a lone "!!!", are reported but do not take part in the
+    /// aggregation.
+    pub fn document_scores(&self, text: &str, aggregation: Aggregation) -> DocumentSentiment {
+        let sentences: Vec<SentenceSentiment> = split_sentences(text)
+            .into_iter()
+            .map(|range| {
+                let sentence = &text[range.0..range.1];
+                let expanded = self.append_emoji_descriptions(sentence);
+                SentenceSentiment {
+                    range,
+                    glyphs: ParsedText::glyphize(&expanded).len(),
+                    scores: self.polarity_scores(sentence),
+                }
+            })
+            .collect();
+
+        let total = aggregate(&sentences, aggregation);
+
+        DocumentSentiment {
+            sentences,
+            aggregation,
+            total,
+        }
+    }
+}
+
+impl Default for Aggregation {
+    fn default() -> Self {
+        Aggregation::Mean
+    }
+}
+
+/// Returns the byte offsets of the sentences of `text`, excluding surrounding whitespace.
+///
+/// A sentence ends at a line break, or at a run of `.`, `!`, `?` or `…` followed by whitespace
+/// or by the end of the text. Closing quotes and brackets right after the run belong to the
+/// sentence. A period does not end a sentence after a common abbreviation ("Mr.", "vs.") or a
+/// dotted one ("U.S.", "e.g.").
+pub fn split_sentences(text: &str) -> Vec<(usize, usize)> {
+    let mut results = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = if c == '\n' {
+            i
+        } else if is_terminator(c) {
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, d)) = chars.peek() {
+                if is_terminator(d) || is_closer(d) {
+                    end = j + d.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            match chars.peek() {
+                Some(&(_, d)) if !d.is_whitespace() => continue,
+                _ => (),
+            }
+            if c == '.' && end == i + 1 && is_abbreviation(&text[start..i]) {
+                continue;
+            }
+
+            end
+        } else {
+            continue;
+        };
+
+        push_trimmed(text, (start, end), &mut results);
+        start = end;
+    }
+    push_trimmed(text, (start, text.len()), &mut results);
+
+    results
+}
+
+const ABBREVIATIONS: [&str; 16] = [
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "inc", "ltd", "co", "jan",
+    "feb", "approx",
+];
+
+fn is_terminator(c: char) -> bool {
+    c == '.' || c == '!' || c == '?' || c == '…'
+}
+
+fn is_closer(c: char) -> bool {
+    "\"')]}”’»".contains(c)
+}
+
+fn is_abbreviation(preceding: &str) -> bool {
+    let word = match preceding.split_whitespace().last() {
+        Some(word) => word.to_lowercase(),
+        None => return false,
+    };
+    word.contains('.') || ABBREVIATIONS.contains(&word.as_str())
+}
+
+fn push_trimmed(text: &str, range: (usize, usize), results: &mut Vec<(usize, usize)>) {
+    let s = &text[range.0..range.1];
+    let start = range.0 + (s.len() - s.trim_start().len());
+    let end = range.0 + s.trim_end().len();
+    if start < end {
+        results.push((start, end));
+    }
+}
+
+fn aggregate(sentences: &[SentenceSentiment], aggregation: Aggregation) -> SentimentScores {
+    let scored = sentences.iter().filter(|s| s.glyphs > 0);
+
+    if let Aggregation::MaxMagnitude = aggregation {
+        return scored
+            .max_by(|a, b| {
+                let (a, b) = (a.scores.compound.abs(), b.scores.compound.abs());
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|s| s.scores)
+            .unwrap_or_default();
+    }
+
+    let mut total = SentimentScores::default();
+    let mut weights = 0f64;
+    for s in scored {
+        let weight = match aggregation {
+            Aggregation::LengthWeighted => s.glyphs as f64,
+            _ => 1.0,
+        };
+        total.neg += s.scores.neg * weight;
+        total.neu += s.scores.neu * weight;
+        total.pos += s.scores.pos * weight;
+        total.compound += s.scores.compound * weight;
+        weights += weight;
+    }
+
+    if weights > 0.0 {
+        total.neg /= weights;
+        total.neu /= weights;
+        total.pos /= weights;
+        total.compound /= weights;
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split(text: &str) -> Vec<&str> {
+        split_sentences(text)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect()
+    }
+
+    fn sentence(glyphs: usize, compound: f64) -> SentenceSentiment {
+        SentenceSentiment {
+            range: (0, 0),
+            glyphs,
+            scores: SentimentScores {
+                compound,
+                ..SentimentScores::default()
+            },
+        }
+    }
+
+    #[test]
+    fn abbreviations() {
+        assert_eq!(
+            split("Mr. Smith met Dr. Jones vs. the U.S. team. Then he left at 3.30 p.m. today."),
+            [
+                "Mr. Smith met Dr. Jones vs. the U.S. team.",
+                "Then he left at 3.30 p.m. today."
+            ]
+        );
+        assert_eq!(
+            split("It was great, etc. Anyway."),
+            ["It was great, etc. Anyway."]
+        );
+    }
+
+    #[test]
+    fn terminators() {
+        assert_eq!(
+            split("Wait... what?! Really\u{2026} \"Stop!\" she said.\nNo period here  "),
+            [
+                "Wait...",
+                "what?!",
+                "Really\u{2026}",
+                "\"Stop!\"",
+                "she said.",
+                "No period here"
+            ]
+        );
+        assert_eq!(split("  "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn aggregation() {
+        let sentences = [sentence(2, 0.5), sentence(6, -0.25), sentence(0, 0.9)];
+        assert_eq!(aggregate(&sentences, Aggregation::Mean).compound, 0.125);
+        assert_eq!(
+            aggregate(&sentences, Aggregation::LengthWeighted).compound,
+            -0.0625
+        );
+        assert_eq!(
+            aggregate(&sentences, Aggregation::MaxMagnitude),
+            sentences[0].scores
+        );
+
+        for &aggregation in &[
+            Aggregation::Mean,
+            Aggregation::LengthWeighted,
+            Aggregation::MaxMagnitude,
+        ] {
+            assert_eq!(aggregate(&[], aggregation), SentimentScores::default());
+        }
+    }
+}
@@ -1,29 +1,41 @@
 //! Error types
-use chrono
-use hyper;
-#[cfg(feature= "native_tls")]
-use native_tls;
-use serde::{Deserialize, Serialize};
-use serde_json;
-use tokio;
-pub use hyper::Error as HyperError;
-#[cfg(feature = "tls")]
-pub use hyper_tls::Error as TlsError;
 
-use std::error::{self, Error as _Error};
+use std::error;
 use std::fmt::{self, Display, Formatter};
 use std::io;
 use std::str::Utf8Error;
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::header::{HeaderMap, ToStrError};
+use hyper::{Body, Response};
+use serde::{Deserialize, Serialize};
 
-use crate::types::StatusCode;
+use crate::filters::StatusCode;
+use crate::rate_limit::RateLimitStatus;
+use crate::util::ReadPrefix;
 
+pub use hyper::Error as HyperError;
+#[cfg(feature = "tls")]
+pub use hyper_tls::Error as TlsError;
 
+/// The maximum number of bytes read from the body of an unsuccessful response.
+pub const MAX_ERROR_BODY: usize = 64 * 1024;
+
+///A collection of errors returned by Twitter.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TwitterErrors {
-    ///A collection of errors returned by Twitter.
+    ///The errors returned by Twitter.
     pub errors: Vec<TwitterErrorCode>,
 }
 
+impl TwitterErrors {
+    ///Returns whether one of the errors has the given code.
+    pub fn contains(&self, code: ErrorCode) -> bool {
+        self.errors.iter().any(|e| e.kind() == code)
+    }
+}
+
 impl fmt::Display for TwitterErrors {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut first = true;
@@ -53,103 +65,125 @@ pub struct TwitterErrorCode {
     pub code: i32,
 }
 
+impl TwitterErrorCode {
+    ///Returns the meaning of `code`.
+    pub fn kind(&self) -> ErrorCode {
+        ErrorCode::from(self.code)
+    }
+}
+
 impl fmt::Display for TwitterErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "#{}: {}", self.code, self.message)
     }
 }
 
-/// Represents an error that can occur during media processing.
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-pub struct MediaError {
-    /// A numeric error code assigned to the error.
-    pub code: i32,
-    /// A short name given to the error.
-    pub name: String,
-    /// The full text of the error message.
-    pub message: String,
+///The meaning of a numeric error code returned by Twitter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    ///32: The request could not be authenticated.
+    AuthenticationFailed,
+    ///34: The requested resource does not exist.
+    NotFound,
+    ///64: The account is suspended.
+    AccountSuspended,
+    ///88: The rate limit for the resource has been reached.
+    RateLimitExceeded,
+    ///89: The access token is invalid or has expired.
+    InvalidToken,
+    ///130: Twitter is temporarily over capacity.
+    OverCapacity,
+    ///131: An unknown internal error occurred.
+    InternalError,
+    ///135: The OAuth timestamp is too far from the server's clock.
+    TimestampOutOfBounds,
+    ///185: The user is over the daily limit of status updates.
+    OverDailyLimit,
+    ///215: The request carried bad authentication data.
+    BadAuthenticationData,
+    ///326: The account is temporarily locked.
+    AccountLocked,
+    ///A code not listed above.
+    Other(i32),
 }
 
+impl From<i32> for ErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            32 => ErrorCode::AuthenticationFailed,
+            34 => ErrorCode::NotFound,
+            64 => ErrorCode::AccountSuspended,
+            88 => ErrorCode::RateLimitExceeded,
+            89 => ErrorCode::InvalidToken,
+            130 => ErrorCode::OverCapacity,
+            131 => ErrorCode::InternalError,
+            135 => ErrorCode::TimestampOutOfBounds,
+            185 => ErrorCode::OverDailyLimit,
+            215 => ErrorCode::BadAuthenticationData,
+            326 => ErrorCode::AccountLocked,
+            code => ErrorCode::Other(code),
+        }
+    }
+}
 
-/// An error occurred while trying to connect to a Stream.
+/// An error occurred while connecting to Twitter or reading a response.
+///
+/// New variants may be added in minor releases. Rather than matching on every variant, use
+/// `is_retryable`, `is_auth` and `is_rate_limit` to decide how to react to an error.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-
-    ///A URL was passed to a shortcut function that didn't match the method being called.
-    BadUrl,
-    ///The response from Twitter was formatted incorrectly or in an unexpected manner. The enclosed
-    ///values are an explanatory string and, if applicable, the input that caused the error.
-    ///
-    ///This usually reflects a bug in this library, as it means I'm not parsing input right.
-    InvalidResponse(&'static str, Option<String>),
-    ///The response from Twitter was missing an expected value.  The enclosed value was the
-    ///expected parameter.
-    ///
-    ///This usually reflects a bug in this library, as it means I'm expecting a value that may not
-    ///always be there, and need to update my parsing to reflect this.
-    MissingValue(&'static str),
-    ///The `Future` being polled has already returned a completed value (or another error). In
-    ///order to retry the request, create the `Future` again.
-    FutureAlreadyCompleted,
-    ///The response from Twitter returned an error structure instead of the expected response. The
-    ///enclosed value was the response from Twitter.
-    TwitterError(TwitterErrors),
-    ///The response returned from Twitter contained an error indicating that the rate limit for
-    ///that method has been reached. The enclosed value is the Unix timestamp in UTC when the next
-    ///rate-limit window will open.
-    RateLimit(i32),
-    ///An attempt to upload a video or gif successfully uploaded the file, but failed in
-    ///post-processing. The enclosed value contains the error message from Twitter.
-    MediaError(MediaError),
-    ///The response from Twitter gave a response code that indicated an error. The enclosed value
-    ///was the response code.
-    ///
-    ///This is only returned if Twitter did not also return an [error code][TwitterErrors] in the
-    ///response body. That check is performed before examining the status code.
-    ///
-    ///[TwitterErrors]: struct.TwitterErrors.html
-    BadStatus(hyper::StatusCode),
-    ///The web request experienced an error. The enclosed error was returned from hyper.
-    NetError(hyper::error::Error),
-    ///The `native_tls` implementation returned an error. The enclosed error was returned from
-    ///`native_tls`.
-    #[cfg(feature = "native_tls")]
-    TlsError(native_tls::Error),
-    ///An error was experienced while processing the response stream. The enclosed error was
-    ///returned from libstd.
-    IOError(std::io::Error),
-    ///An error occurred while loading the JSON response. The enclosed error was returned from
-    ///`serde_json`.
-    DeserializeError(serde_json::Error),
-    ///An error occurred when parsing a timestamp from Twitter. The enclosed error was returned
-    ///from chrono.
-    TimestampParseError(chrono::ParseError),
-    ///The tokio `Timer` instance was shut down while waiting on a timer, for example while waiting
-    ///for media to be processed by Twitter. The enclosed error was returned from `tokio`.
-    TimerShutdownError(tokio::timer::Error),
-    ///An error occurred when reading the value from a response header. The enclused error was
-    ///returned from hyper.
-    ///
-    ///This error should be considerably rare, but is included to ensure that egg-mode doesn't
-    ///panic if it receives malformed headers or the like.
-    HeaderParseError(hyper::header::ToStrError),
-    ///An error occurred when converting a rate-limit header to an integer. The enclosed error was
-    ///returned from the standard library.
-    ///
-    ///This error should be considerably rare, but is included to ensure that egg-mode doesn't
-    ///panic if it receives malformed headers or the like.
-    HeaderConvertError(std::num::ParseIntError),
-
-    /// An error occured while decoding Brotli stream from the server.
-    Brotli(io::Error),
-    /// An HTTP error from the Stream.
-    Http(StatusCode),
-    /// An error from the `hyper` crate.
+    /// Twitter answered with an unsuccessful status and a JSON error object. The enclosed values
+    /// are the status code and the errors returned by Twitter.
+    TwitterError(StatusCode, TwitterErrors),
+    /// Twitter answered `420 Enhance Your Calm` or `429 Too Many Requests`, indicating that the
+    /// rate limit for that method has been reached or that the client connected too often. The
+    /// enclosed value holds the rate limit headers of the response, including when the next
+    /// rate-limit window will open.
+    RateLimit(RateLimitStatus),
+    /// Twitter answered with an unsuccessful status. The enclosed values are the status code and
+    /// the body of the response, truncated to `MAX_ERROR_BODY` bytes, which did not hold a
+    /// Twitter error object.
+    Http(StatusCode, Bytes),
+    /// An error from the `hyper` crate, e.g. the connection was refused or reset.
     Hyper(HyperError),
-    /// The stream has timed out.
-    TimedOut,
+    /// An I/O error, e.g. while reading a credentials file.
+    IOError(io::Error),
+    /// An error occured while decoding the compressed stream from the server.
+    Decode(io::Error),
+    /// The server used a `Content-Encoding` which cannot be decoded. The enclosed value is the
+    /// value of the header.
+    UnsupportedEncoding(String),
+    /// The connection could not be established, or the response headers were not received,
+    /// within the enclosed duration.
+    ConnectTimeout(Duration),
+    /// The response headers were received but the body did not start within the enclosed
+    /// duration.
+    FirstByteTimeout(Duration),
+    /// The stream did not receive any data, not even a keep-alive newline, within the enclosed
+    /// duration.
+    InactivityTimeout(Duration),
     /// Twitter returned a non-UTF-8 string.
     Utf8(Utf8Error),
+    /// A response could not be deserialized. The enclosed error was returned from `serde_json`.
+    DeserializeError(serde_json::Error),
+    /// A response was missing an expected value, or a credential was missing from the
+    /// environment. The enclosed value is the name of the missing value.
+    MissingValue(&'static str),
+    /// A timestamp from Twitter could not be parsed. The enclosed error was returned from
+    /// chrono.
+    TimestampParseError(chrono::ParseError),
+    /// A response header was not visible ASCII.
+    HeaderParseError(ToStrError),
+    /// A rate limit header was not an integer.
+    HeaderConvertError(std::num::ParseIntError),
+    /// A `ReconnectingStream` gave up after the enclosed number of consecutive failed attempts,
+    /// the last of which ended with the server closing the connection.
+    MaxAttempts(u32),
+    /// The `Future` being polled has already returned a completed value (or another error). In
+    /// order to retry the request, create the `Future` again.
+    FutureAlreadyCompleted,
     /// User-defined error.
     Custom(Box<dyn error::Error + Send + Sync>),
 }
@@ -161,88 +195,181 @@ impl Error {
     {
         Error::Custom(error.into())
     }
-}
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    /// Returns the status code of the response, if the error came from an unsuccessful one.
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self {
+            Error::TwitterError(status, _) | Error::Http(status, _) => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Returns whether sending the request again, or reconnecting, may succeed.
+    ///
+    /// Network errors, timeouts, server errors and rate limits are retryable, though rate
+    /// limits call for waiting first. Authentication failures, other client errors and errors
+    /// in parsing a response are not.
+    ///
+    /// This returns `true` whenever `is_rate_limit` does, even if the status, such as the 403
+    /// accompanying error code 185, is not retryable otherwise.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::TwitterError(status, _) | Error::Http(status, _) => {
+                if self.is_rate_limit() {
+                    return true;
+                }
+                if self.is_auth() {
+                    return false;
+                }
+                match status.as_u16() {
+                    401 | 403 | 404 | 406 | 413 | 416 => false,
+                    _ => true,
+                }
+            }
+            Error::RateLimit(_)
+            | Error::Hyper(_)
+            | Error::IOError(_)
+            | Error::Decode(_)
+            | Error::ConnectTimeout(_)
+            | Error::FirstByteTimeout(_)
+            | Error::InactivityTimeout(_)
+            | Error::Utf8(_) => true,
+            Error::UnsupportedEncoding(_)
+            | Error::DeserializeError(_)
+            | Error::MissingValue(_)
+            | Error::TimestampParseError(_)
+            | Error::HeaderParseError(_)
+            | Error::HeaderConvertError(_)
+            | Error::MaxAttempts(_)
+            | Error::FutureAlreadyCompleted
+            | Error::Custom(_) => false,
+        }
+    }
+
+    /// Returns whether Twitter rejected the credentials of the request.
+    pub fn is_auth(&self) -> bool {
         match *self {
-            Error::BadUrl => write!(f, "URL given did not match API method"),
-            Error::InvalidResponse(err, ref ext) => {
-                write!(f, "Invalid response received: {} ({:?})", err, ext)
+            Error::TwitterError(status, ref errors) => {
+                status == StatusCode::UNAUTHORIZED
+                    || errors.errors.iter().any(|e| match e.kind() {
+                        ErrorCode::AuthenticationFailed
+                        | ErrorCode::InvalidToken
+                        | ErrorCode::TimestampOutOfBounds
+                        | ErrorCode::BadAuthenticationData => true,
+                        _ => false,
+                    })
             }
-            Error::MissingValue(val) => write!(f, "Value missing from response: {}", val),
-            Error::FutureAlreadyCompleted => write!(f, "Future has already been completed"),
-            Error::TwitterError(ref err) => write!(f, "Error(s) returned from Twitter: {}", err),
-            Error::RateLimit(ts) => write!(f, "Rate limit reached, hold until {}", ts),
-            Error::MediaError(ref err) => write!(f, "Error processing media: {}", err.message),
-            Error::BadStatus(ref val) => write!(f, "Error status received: {}", val),
-            Error::NetError(ref err) => write!(f, "Network error: {}", err),
-            #[cfg(feature = "native_tls")]
-            Error::TlsError(ref err) => write!(f, "TLS error: {}", err),
-            Error::IOError(ref err) => write!(f, "IO error: {}", err),
-            Error::DeserializeError(ref err) => write!(f, "JSON deserialize error: {}", err),
-            Error::TimestampParseError(ref err) => write!(f, "Error parsing timestamp: {}", err),
-            Error::TimerShutdownError(ref err) => write!(f, "Timer runtime shutdown: {}", err),
-            Error::HeaderParseError(ref err) => write!(f, "Error decoding header: {}", err),
-            Error::HeaderConvertError(ref err) => write!(f, "Error converting header: {}", err),
+            Error::Http(status, _) => status == StatusCode::UNAUTHORIZED,
+            _ => false,
         }
     }
-}
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
+    /// Returns whether a rate limit was reached, in which case the request should only be
+    /// retried after waiting.
+    pub fn is_rate_limit(&self) -> bool {
         match *self {
-            Error::BadUrl => "URL given did not match API method",
-            Error::InvalidResponse(_, _) => "Invalid response received",
-            Error::MissingValue(_) => "Value missing from response",
-            Error::FutureAlreadyCompleted => "Future has already been completed",
-            Error::TwitterError(_) => "Error returned from Twitter",
-            Error::RateLimit(_) => "Rate limit for method reached",
-            Error::MediaError(_) => "Error processing media",
-            Error::BadStatus(_) => "Response included error code",
-            Error::NetError(ref err) => err.description(),
-            #[cfg(feature = "native_tls")]
-            Error::TlsError(ref err) => err.description(),
-            Error::IOError(ref err) => err.description(),
-            Error::DeserializeError(ref err) => err.description(),
-            Error::TimestampParseError(ref err) => err.description(),
-            Error::TimerShutdownError(ref err) => err.description(),
-            Error::HeaderParseError(ref err) => err.description(),
-            Error::HeaderConvertError(ref err) => err.description(),
+            Error::RateLimit(_) => true,
+            Error::TwitterError(status, ref errors) => {
+                is_rate_limit_status(status)
+                    || errors.contains(ErrorCode::RateLimitExceeded)
+                    || errors.contains(ErrorCode::OverDailyLimit)
+            }
+            Error::Http(status, _) => is_rate_limit_status(status),
+            _ => false,
+        }
+    }
+
+    /// Builds the error for an unsuccessful response from its status, headers and the start of
+    /// its body.
+    pub(crate) fn from_response(status: StatusCode, headers: &HeaderMap, body: Bytes) -> Self {
+        if is_rate_limit_status(status) {
+            // Malformed headers must not hide the rate limit, which calls for backing off.
+            let status = RateLimitStatus::from_headers_lossy(headers).0;
+            return Error::RateLimit(status);
+        }
+        match serde_json::from_slice(&body) {
+            Ok(errors) => Error::TwitterError(status, errors),
+            Err(_) => Error::Http(status, body),
         }
     }
 
-    fn cause(&self) -> Option<&dyn std::error::Error> {
+    /// Reads at most `MAX_ERROR_BODY` bytes of an unsuccessful response and builds the error.
+    pub(crate) async fn read_response(res: Response<Body>) -> Self {
+        let (parts, body) = res.into_parts();
+        let body = ReadPrefix::new(body, MAX_ERROR_BODY).await;
+        Error::from_response(parts.status, &parts.headers, body)
+    }
+}
+
+fn is_rate_limit_status(status: StatusCode) -> bool {
+    status.as_u16() == 420 || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
-            Error::NetError(ref err) => Some(err),
-            #[cfg(feature = "native_tls")]
-            Error::TlsError(ref err) => Some(err),
-            Error::IOError(ref err) => Some(err),
-            Error::TimestampParseError(ref err) => Some(err),
-            Error::DeserializeError(ref err) => Some(err),
-            Error::TimerShutdownError(ref err) => Some(err),
-            Error::HeaderParseError(ref err) => Some(err),
-            Error::HeaderConvertError(ref err) => Some(err),
-            _ => None,
+            Error::TwitterError(status, ref errors) => {
+                write!(f, "error(s) returned from Twitter ({}): {}", status, errors)
+            }
+            Error::RateLimit(ref status) => write!(f, "rate limit reached: {}", status),
+            Error::Http(status, _) => Display::fmt(&status, f),
+            Error::Hyper(ref e) => Display::fmt(e, f),
+            Error::IOError(ref e) => write!(f, "I/O error: {}", e),
+            Error::Decode(ref e) => write!(f, "error decoding the response body: {}", e),
+            Error::UnsupportedEncoding(ref encoding) => {
+                write!(f, "unsupported Content-Encoding: {}", encoding)
+            }
+            Error::ConnectTimeout(dur) => write!(f, "timed out while connecting after {:?}", dur),
+            Error::FirstByteTimeout(dur) => {
+                write!(f, "no data received {:?} after connecting", dur)
+            }
+            Error::InactivityTimeout(dur) => write!(f, "no data received for {:?}", dur),
+            Error::Utf8(ref e) => Display::fmt(e, f),
+            Error::DeserializeError(ref e) => write!(f, "JSON deserialize error: {}", e),
+            Error::MissingValue(val) => write!(f, "value missing: {}", val),
+            Error::TimestampParseError(ref e) => write!(f, "error parsing timestamp: {}", e),
+            Error::HeaderParseError(ref e) => write!(f, "error decoding header: {}", e),
+            Error::HeaderConvertError(ref e) => write!(f, "error converting header: {}", e),
+            Error::MaxAttempts(n) => write!(f, "gave up after {} failed attempts", n),
+            Error::FutureAlreadyCompleted => f.write_str("future has already been completed"),
+            Error::Custom(ref e) => Display::fmt(e, f),
         }
     }
 }
 
-impl From<hyper::error::Error> for Error {
-    fn from(err: hyper::error::Error) -> Error {
-        Error::NetError(err)
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Hyper(ref e) => Some(e),
+            Error::IOError(ref e) | Error::Decode(ref e) => Some(e),
+            Error::Utf8(ref e) => Some(e),
+            Error::DeserializeError(ref e) => Some(e),
+            Error::TimestampParseError(ref e) => Some(e),
+            Error::HeaderParseError(ref e) => Some(e),
+            Error::HeaderConvertError(ref e) => Some(e),
+            Error::Custom(ref e) => Some(&**e),
+            Error::TwitterError(..)
+            | Error::RateLimit(_)
+            | Error::Http(..)
+            | Error::UnsupportedEncoding(_)
+            | Error::ConnectTimeout(_)
+            | Error::FirstByteTimeout(_)
+            | Error::InactivityTimeout(_)
+            | Error::MissingValue(_)
+            | Error::MaxAttempts(_)
+            | Error::FutureAlreadyCompleted => None,
+        }
     }
 }
 
-#[cfg(feature = "native_tls")]
-impl From<native_tls::Error> for Error {
-    fn from(err: native_tls::Error) -> Error {
-        Error::TlsError(err)
+impl From<HyperError> for Error {
+    fn from(err: HyperError) -> Error {
+        Error::Hyper(err)
     }
 }
 
-impl From<std::io::Error> for Error {
-    fn from(err: std::io::Error) -> Error {
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
         Error::IOError(err)
     }
 }
@@ -259,14 +386,8 @@ impl From<chrono::ParseError> for Error {
     }
 }
 
-impl From<tokio::timer::Error> for Error {
-    fn from(err: tokio::timer::Error) -> Error {
-        Error::TimerShutdownError(err)
-    }
-}
-
-impl From<hyper::header::ToStrError> for Error {
-    fn from(err: hyper::header::ToStrError) -> Error {
+impl From<ToStrError> for Error {
+    fn from(err: ToStrError) -> Error {
         Error::HeaderParseError(err)
     }
 }
@@ -277,45 +398,82 @@ impl From<std::num::ParseIntError> for Error {
     }
 }
 
-impl error::Error for Error {
-    fn description(&self) -> &str {
-        use crate::Error::*;
+#[cfg(test)]
+mod test {
+    use super::*;
 
-        match *self {
-            Brotli(ref e) => e.description(),
-            Http(ref status) => status.canonical_reason().unwrap_or("<unknown status code>"),
-            Hyper(ref e) => e.description(),
-            TimedOut => "timed out",
-            Utf8(ref e) => e.description(),
-            Custom(ref e) => e.description(),
-        }
-    }
+    use std::error::Error as _;
 
-    fn cause(&self) -> Option<&dyn error::Error> {
-        use crate::Error::*;
+    use hyper::header::HeaderValue;
 
-        match *self {
-            Brotli(ref e) => Some(e),
-            Http(_) | TimedOut => None,
-            Hyper(ref e) => Some(e),
-            Utf8(ref e) => Some(e),
-            Custom(ref e) => Some(&**e),
-        }
+    const AUTH_FAILED: &str = r#"{"errors":[{"code":32,"message":"Could not authenticate you."}]}"#;
+
+    fn response(code: u16, body: &'static str) -> Error {
+        let status = StatusCode::from_u16(code).unwrap();
+        Error::from_response(
+            status,
+            &HeaderMap::new(),
+            Bytes::from_static(body.as_bytes()),
+        )
     }
-}
 
-impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        use crate::Error::*;
+    #[test]
+    fn from_response() {
+        match response(401, AUTH_FAILED) {
+            Error::TwitterError(status, errors) => {
+                assert_eq!(status, StatusCode::UNAUTHORIZED);
+                assert_eq!(errors.errors[0].kind(), ErrorCode::AuthenticationFailed);
+            }
+            e => panic!("expected a Twitter error, got {:?}", e),
+        }
+        match response(503, "<html>Over capacity</html>") {
+            Error::Http(status, body) => {
+                assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+                assert_eq!(&body[..], &b"<html>Over capacity</html>"[..]);
+            }
+            e => panic!("expected an HTTP error, got {:?}", e),
+        }
+        match response(420, "") {
+            Error::RateLimit(_) => (),
+            e => panic!("expected a rate limit error, got {:?}", e),
+        }
 
-        match *self {
-            Brotli(ref e) => Display::fmt(e, f),
-            Http(ref code) => Display::fmt(code, f),
-            Hyper(ref e) => Display::fmt(e, f),
-            TimedOut => Display::fmt(self.description(), f),
-            Utf8(ref e) => Display::fmt(e, f),
-            Custom(ref e) => Display::fmt(e, f),
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rate-limit-remaining", HeaderValue::from_static("none"));
+        let error = Error::from_response(StatusCode::TOO_MANY_REQUESTS, &headers, Bytes::new());
+        match error {
+            Error::RateLimit(status) => assert_eq!(status, RateLimitStatus::default()),
+            ref e => panic!("expected a rate limit error, got {:?}", e),
         }
+        assert!(error.is_retryable());
     }
-}
 
+    #[test]
+    fn classify() {
+        let auth = response(401, AUTH_FAILED);
+        assert!(auth.is_auth());
+        assert!(!auth.is_retryable());
+        assert!(!auth.is_rate_limit());
+
+        let limited = response(403, r#"{"errors":[{"code":185,"message":""}]}"#);
+        assert!(limited.is_rate_limit());
+        assert!(limited.is_retryable());
+        assert!(!limited.is_auth());
+
+        let unavailable = response(503, "");
+        assert!(unavailable.is_retryable());
+        assert!(!unavailable.is_auth());
+
+        let timeout = Error::InactivityTimeout(Duration::from_secs(90));
+        assert!(timeout.is_retryable());
+        assert_eq!(timeout.status(), None);
+
+        let encoding = Error::UnsupportedEncoding("compress".to_string());
+        assert!(!encoding.is_retryable());
+
+        let json = serde_json::from_str::<TwitterErrors>("{").unwrap_err();
+        let error = Error::from(json);
+        assert!(!error.is_retryable());
+        assert!(error.source().is_some());
+    }
+}
@@ -0,0 +1,178 @@
+//! Step-by-step traces of how `SentimentIntensityAnalyzer` scores a text.
+//!
+//! `SentimentIntensityAnalyzer::polarity_scores_explained` returns an `Explanation` holding,
+//! for every glyph of the text, the valence found in the lexicon and each heuristic that
+//! adjusted it afterwards, in the order they were applied.
+
+use serde::Serialize;
+
+use crate::sentiment::SentimentScores;
+
+/// The trace of a call to `SentimentIntensityAnalyzer::polarity_scores_explained`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Explanation {
+    /// The text that was scored, after emoji were replaced by their descriptions.
+    pub text: String,
+    /// One entry per glyph of the text.
+    pub glyphs: Vec<GlyphTrace>,
+    /// The amount added to the total sentiment for `!` and `?` marks.
+    pub punctuation_amplifier: f64,
+    /// The resulting scores, identical to what `polarity_scores` returns.
+    pub scores: SentimentScores,
+}
+
+/// How the valence of a single glyph was computed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GlyphTrace {
+    /// The glyph, stripped of surrounding punctuation.
+    pub glyph: String,
+    /// The valence of the glyph in the lexicon, or `None` if the glyph is not in the lexicon.
+    pub lexicon_valence: Option<f64>,
+    /// The adjustments applied to the lexicon valence, in order.
+    pub adjustments: Vec<Adjustment>,
+    /// The final valence contributed by the glyph.
+    pub valence: f64,
+}
+
+/// A heuristic that adjusted the valence of a glyph.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Adjustment {
+    /// The glyph is in all caps while the rest of the text is not.
+    Caps {
+        /// The amount added to the valence.
+        delta: f64,
+    },
+    /// A booster ("very", "barely", "kind of", ...) precedes the glyph.
+    Booster {
+        /// The booster.
+        glyph: String,
+        /// How many glyphs before this one the booster starts.
+        distance: usize,
+        /// The amount added to the valence.
+        delta: f64,
+    },
+    /// A negation ("not", "never", ...) or emphasis ("never so") precedes the glyph.
+    Negation {
+        /// The glyph that triggered the adjustment.
+        glyph: String,
+        /// How many glyphs before this one the triggering glyph is.
+        distance: usize,
+        /// The factor the valence was multiplied with.
+        factor: f64,
+    },
+    /// The glyph is part of an idiom, whose valence replaces the valence of its glyphs.
+    Idiom {
+        /// The idiom.
+        idiom: String,
+        /// The amount added to the valence.
+        delta: f64,
+    },
+    /// The glyph is preceded by "least".
+    Least {
+        /// The factor the valence was multiplied with.
+        factor: f64,
+    },
+    /// The text contains a contrastive conjunction ("but"), de-emphasizing the glyphs before it
+    /// and emphasizing the glyphs after it.
+    But {
+        /// The factor the valence was multiplied with.
+        factor: f64,
+    },
+}
+
+impl GlyphTrace {
+    pub(crate) fn new(glyph: &str) -> Self {
+        GlyphTrace {
+            glyph: glyph.to_string(),
+            lexicon_valence: None,
+            adjustments: Vec::new(),
+            valence: 0.0,
+        }
+    }
+}
+
+/// Appends the adjustment built by `f` to `trace`, if tracing is enabled.
+pub(crate) fn record<F>(trace: &mut Option<&mut GlyphTrace>, f: F)
+where
+    F: FnOnce() -> Adjustment,
+{
+    if let Some(ref mut trace) = *trace {
+        trace.adjustments.push(f());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{SentimentIntensityAnalyzer, B_INCR, NEGATION_SCALAR};
+
+    #[test]
+    fn scores() {
+        let analyzer = SentimentIntensityAnalyzer::new();
+        for text in &[
+            "",
+            "The food is very good, but the service is not great!!",
+            "I feel under the weather \u{1f61e}",
+            "VADER is smart, handsome, and FUNNY.",
+        ] {
+            let explanation = analyzer.polarity_scores_explained(text);
+            assert_eq!(
+                explanation.scores,
+                analyzer.polarity_scores(text),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn trace() {
+        let analyzer = SentimentIntensityAnalyzer::new();
+        let explanation = analyzer
+            .polarity_scores_explained("The food is very good, but the service is not great");
+        let glyphs: Vec<_> = explanation.glyphs.iter().map(|t| &*t.glyph).collect();
+        assert_eq!(
+            glyphs,
+            ["The", "food", "is", "very", "good", "but", "the", "service", "is", "not", "great"]
+        );
+
+        let good = &explanation.glyphs[4];
+        let valence = good.lexicon_valence.unwrap() + B_INCR;
+        assert_eq!(
+            good.adjustments,
+            [
+                Adjustment::Booster {
+                    glyph: "very".into(),
+                    distance: 1,
+                    delta: B_INCR,
+                },
+                Adjustment::But { factor: 0.5 },
+            ]
+        );
+        assert_eq!(good.valence, valence * 0.5);
+
+        let great = &explanation.glyphs[10];
+        let lexicon_valence = great.lexicon_valence.unwrap();
+        let valence = lexicon_valence * NEGATION_SCALAR;
+        assert_eq!(
+            great.adjustments,
+            [
+                Adjustment::Negation {
+                    glyph: "not".into(),
+                    distance: 1,
+                    factor: valence / lexicon_valence,
+                },
+                Adjustment::But {
+                    factor: valence * 1.5 / valence,
+                },
+            ]
+        );
+        assert_eq!(great.valence, valence * 1.5);
+
+        let but = &explanation.glyphs[5];
+        assert_eq!(but.lexicon_valence, None);
+        assert!(but.adjustments.is_empty());
+        assert_eq!(but.valence, 0.0);
+    }
+}
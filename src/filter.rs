@@ -87,3 +87,283 @@ impl ::std::fmt::Display for FilterLevel {
         }
     }
 
+}
+
+/// The maximum number of phrases a filtered stream can track.
+pub const MAX_TRACK: usize = 400;
+/// The maximum number of users a filtered stream can follow.
+pub const MAX_FOLLOW: usize = 5_000;
+/// The maximum number of bounding boxes of a filtered stream.
+pub const MAX_LOCATIONS: usize = 25;
+/// The maximum length of a tracked phrase, in bytes.
+pub const MAX_PHRASE_LEN: usize = 60;
+
+/// A bounding box, in the form of `((west_longitude, south_latitude), (east_longitude,
+/// north_latitude))`.
+pub type BoundingBox = ((f64, f64), (f64, f64));
+
+/// The `track`, `follow` and `locations` parameters of a filtered stream.
+///
+/// Unlike the setters of `StreamBuilder`, which borrow their values for the life of the builder,
+/// `FilterParams` owns them, so it can be kept around and modified, e.g. to change the filter of
+/// a `ManagedStream` while it runs.
+///
+/// The builders check each value against the documented limits of the Streaming API and return
+/// a `FilterError` instead of adding it if it would be rejected, so that mistakes are caught
+/// before connecting.
+///
+/// ### Example
+///
+/// ```rust
+/// use khipu::filters::{FilterError, FilterParams};
+///
+/// # fn main() -> Result<(), FilterError> {
+/// let mut params = FilterParams::new();
+/// params
+///     .track_phrase("rustlang")?
+///     .follow_user(6253282)?
+///     .bounding_box((-122.75, 36.8), (-121.75, 37.8))?;
+///
+/// assert!(params.bounding_box((-121.75, 37.8), (-122.75, 36.8)).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterParams {
+    track: String,
+    track_len: usize,
+    follow: Vec<u64>,
+    locations: Vec<BoundingBox>,
+}
+
+/// An invalid filter parameter, returned by the builders of `FilterParams`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterError {
+    /// A phrase is empty or only made of whitespace.
+    EmptyPhrase,
+    /// A phrase contains a comma, which separates phrases in the `track` parameter.
+    /// The enclosed value is the phrase.
+    CommaInPhrase(String),
+    /// A phrase is longer than `MAX_PHRASE_LEN` bytes. The enclosed value is the phrase.
+    PhraseTooLong(String),
+    /// Adding the phrase would exceed `MAX_TRACK` phrases.
+    TooManyPhrases,
+    /// Adding the user would exceed `MAX_FOLLOW` users.
+    TooManyUsers,
+    /// Adding the bounding box would exceed `MAX_LOCATIONS` bounding boxes.
+    TooManyLocations,
+    /// A longitude is outside of -180 to 180, or a latitude outside of -90 to 90.
+    /// The enclosed value is the bounding box.
+    OutOfRange(BoundingBox),
+    /// The south-west corner of a bounding box is not to the south-west of its north-east
+    /// corner, e.g. because the corners or the coordinates were swapped.
+    /// The enclosed value is the bounding box.
+    CornersReversed(BoundingBox),
+}
+
+impl FilterParams {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a phrase to track. The terms of a phrase are separated by spaces, and a Tweet matches
+    /// the phrase if it contains all of them. Adding a phrase which is already tracked has no
+    /// effect.
+    pub fn track_phrase(&mut self, phrase: &str) -> Result<&mut Self, FilterError> {
+        let phrase = phrase.trim();
+        if phrase.is_empty() {
+            return Err(FilterError::EmptyPhrase);
+        }
+        if phrase.contains(',') {
+            return Err(FilterError::CommaInPhrase(phrase.to_owned()));
+        }
+        if phrase.len() > MAX_PHRASE_LEN {
+            return Err(FilterError::PhraseTooLong(phrase.to_owned()));
+        }
+        if self.track_phrases().any(|p| p == phrase) {
+            return Ok(self);
+        }
+        if self.track_len == MAX_TRACK {
+            return Err(FilterError::TooManyPhrases);
+        }
+
+        if !self.track.is_empty() {
+            self.track.push(',');
+        }
+        self.track.push_str(phrase);
+        self.track_len += 1;
+        Ok(self)
+    }
+
+    /// Add a user whose Tweets to follow. Adding a user who is already followed has no effect.
+    pub fn follow_user(&mut self, id: u64) -> Result<&mut Self, FilterError> {
+        if self.follow.contains(&id) {
+            return Ok(self);
+        }
+        if self.follow.len() == MAX_FOLLOW {
+            return Err(FilterError::TooManyUsers);
+        }
+
+        self.follow.push(id);
+        Ok(self)
+    }
+
+    /// Add a bounding box to filter Tweets by, given its south-west and north-east corners as
+    /// `(longitude, latitude)` pairs.
+    pub fn bounding_box(
+        &mut self,
+        south_west: (f64, f64),
+        north_east: (f64, f64),
+    ) -> Result<&mut Self, FilterError> {
+        let bbox = (south_west, north_east);
+        let in_range = |(lon, lat): (f64, f64)| {
+            (-180.0..=180.0).contains(&lon) && (-90.0..=90.0).contains(&lat)
+        };
+        if !in_range(south_west) || !in_range(north_east) {
+            return Err(FilterError::OutOfRange(bbox));
+        }
+        if south_west.0 >= north_east.0 || south_west.1 >= north_east.1 {
+            return Err(FilterError::CornersReversed(bbox));
+        }
+        if self.locations.len() == MAX_LOCATIONS {
+            return Err(FilterError::TooManyLocations);
+        }
+
+        self.locations.push(bbox);
+        Ok(self)
+    }
+
+    /// Returns the tracked phrases as a comma separated list, as sent to the API.
+    pub fn track(&self) -> &str {
+        &self.track
+    }
+
+    /// Iterates over the tracked phrases.
+    pub fn track_phrases(&self) -> impl Iterator<Item = &str> {
+        self.track.split(',').filter(|p| !p.is_empty())
+    }
+
+    /// Returns the followed user IDs.
+    pub fn follow(&self) -> &[u64] {
+        &self.follow
+    }
+
+    /// Returns the bounding boxes.
+    pub fn locations(&self) -> &[BoundingBox] {
+        &self.locations
+    }
+
+    /// Returns whether no parameter is set.
+    pub fn is_empty(&self) -> bool {
+        self.track.is_empty() && self.follow.is_empty() && self.locations.is_empty()
+    }
+}
+
+impl std::fmt::Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            FilterError::EmptyPhrase => f.write_str("Empty track phrase"),
+            FilterError::CommaInPhrase(ref phrase) => {
+                write!(f, "Track phrase contains a comma: {:?}", phrase)
+            }
+            FilterError::PhraseTooLong(ref phrase) => write!(
+                f,
+                "Track phrase is longer than {} bytes: {:?}",
+                MAX_PHRASE_LEN, phrase
+            ),
+            FilterError::TooManyPhrases => write!(f, "More than {} track phrases", MAX_TRACK),
+            FilterError::TooManyUsers => write!(f, "More than {} users to follow", MAX_FOLLOW),
+            FilterError::TooManyLocations => {
+                write!(f, "More than {} bounding boxes", MAX_LOCATIONS)
+            }
+            FilterError::OutOfRange(bbox) => {
+                write!(f, "Coordinates out of range in bounding box {:?}", bbox)
+            }
+            FilterError::CornersReversed(bbox) => {
+                write!(f, "Corners are reversed in bounding box {:?}", bbox)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn track_phrase() {
+        let mut params = FilterParams::new();
+        params
+            .track_phrase("rustlang")
+            .unwrap()
+            .track_phrase(" async await ")
+            .unwrap()
+            .track_phrase("rustlang")
+            .unwrap();
+        assert_eq!(params.track(), "rustlang,async await");
+        assert_eq!(params.track_phrases().count(), 2);
+
+        assert_eq!(params.track_phrase("  "), Err(FilterError::EmptyPhrase));
+        assert_eq!(
+            params.track_phrase("a,b"),
+            Err(FilterError::CommaInPhrase("a,b".to_owned()))
+        );
+        let long = "a".repeat(MAX_PHRASE_LEN + 1);
+        assert_eq!(
+            params.track_phrase(&long),
+            Err(FilterError::PhraseTooLong(long.clone()))
+        );
+
+        for i in 2..MAX_TRACK {
+            params.track_phrase(&i.to_string()).unwrap();
+        }
+        assert_eq!(
+            params.track_phrase("one more"),
+            Err(FilterError::TooManyPhrases)
+        );
+        // Already tracked.
+        assert!(params.track_phrase("rustlang").is_ok());
+    }
+
+    #[test]
+    fn follow_user() {
+        let mut params = FilterParams::new();
+        for id in 0..MAX_FOLLOW as u64 {
+            params.follow_user(id).unwrap();
+        }
+        assert!(params.follow_user(0).is_ok());
+        assert_eq!(params.follow_user(6253282), Err(FilterError::TooManyUsers));
+        assert_eq!(params.follow().len(), MAX_FOLLOW);
+    }
+
+    #[test]
+    fn bounding_box() {
+        let mut params = FilterParams::new();
+        params
+            .bounding_box((-122.75, 36.8), (-121.75, 37.8))
+            .unwrap();
+
+        let swapped = ((36.8, -122.75), (37.8, -121.75));
+        assert_eq!(
+            params.bounding_box(swapped.0, swapped.1),
+            Err(FilterError::OutOfRange(swapped))
+        );
+        let reversed = ((-121.75, 37.8), (-122.75, 36.8));
+        assert_eq!(
+            params.bounding_box(reversed.0, reversed.1),
+            Err(FilterError::CornersReversed(reversed))
+        );
+        assert!(params.bounding_box((f64::NAN, 0.0), (1.0, 1.0)).is_err());
+
+        for _ in 1..MAX_LOCATIONS {
+            params.bounding_box((-74.0, 40.0), (-73.0, 41.0)).unwrap();
+        }
+        assert_eq!(
+            params.bounding_box((-74.0, 40.0), (-73.0, 41.0)),
+            Err(FilterError::TooManyLocations)
+        );
+        assert_eq!(params.locations().len(), MAX_LOCATIONS);
+    }
+}
@@ -12,6 +12,9 @@ use std::borrow::Borrow;
 //!
 //! The [official guide](https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/connecting) has more information.
 use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "credentials")]
+use std::path::Path;
 use std::str::FromStr;
 use std::{self, io};
 
@@ -20,19 +23,14 @@ use hyper::client::ResponseFuture;
 use hyper::{Body, Request};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
+#[cfg(feature = "credentials")]
+use serde::{Serialize, Serializer};
 use serde_json;
 
 use cfg_if::cfg_if;
 use oauth::Credentials;
 
-/// An OAuth glyph used to log into Twitter.
-#[cfg_attr(
-    feature = "tweetust",
-    doc = "
-
-This implements `tweetust::conn::Authenticator` so you can pass it to
-`tweetust::TwitterClient` as if it were `tweetust::OAuthAuthenticator`")]
-
+use crate::error;
 
 /// A `Stream` that represents a connection to the Twitter Streaming API.
 #[must_use = "Streams are lazy and do nothing unless polled"]
@@ -74,7 +72,7 @@ impl Stream for TwitterStream {
                     let status = resp.status();
                     if !status.is_success() {
                         //TODO: should i try to pull the response regardless?
-                        return Err(error::Error::BadStatus(status));
+                        return Err(error::Error::Http(status, Default::default()));
                     }
 
                     self.body = Some(resp.into_body());
@@ -125,8 +123,20 @@ impl Stream for TwitterStream {
     }
 }
 
+/// An OAuth glyph used to log into Twitter.
+#[cfg_attr(
+    feature = "tweetust",
+    doc = "
 
-#[derive(Copy, Clone, Debug)]
+This implements `tweetust::conn::Authenticator` so you can pass it to
+`tweetust::TwitterClient` as if it were `tweetust::OAuthAuthenticator`")]
+///
+/// The `Debug` representation of a `Glyph` shows the identifiers but not the secrets, so that
+/// it can be logged safely.
+///
+/// With the `credentials` feature, a `Glyph` can be (de)serialized with serde, as a map with the
+/// keys `consumer_key`, `consumer_secret`, `access_key` and `access_secret`.
+#[derive(Copy, Clone)]
 pub struct Glyph<C = String, T = String> {
     pub client: Credentials<C>,
     pub glyph: Credentials<T>,
@@ -154,6 +164,114 @@ impl<C: Borrow<str>, T: Borrow<str>> Glyph<C, T> {
     }
 }
 
+impl Glyph {
+    /// Read a `Glyph` from the environment variables `TWITTER_CONSUMER_KEY`,
+    /// `TWITTER_CONSUMER_SECRET`, `TWITTER_ACCESS_TOKEN` and `TWITTER_ACCESS_TOKEN_SECRET`.
+    pub fn from_env() -> Result<Self, error::Error> {
+        fn var(name: &'static str) -> Result<String, error::Error> {
+            std::env::var(name).map_err(|_| error::Error::MissingValue(name))
+        }
+
+        Ok(Glyph::new(
+            var("TWITTER_CONSUMER_KEY")?,
+            var("TWITTER_CONSUMER_SECRET")?,
+            var("TWITTER_ACCESS_TOKEN")?,
+            var("TWITTER_ACCESS_TOKEN_SECRET")?,
+        ))
+    }
+
+    /// Read a `Glyph` from a TOML file if `path` ends with `.toml`, or from a JSON file
+    /// otherwise.
+    #[cfg(feature = "credentials")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, error::Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)?;
+        if path.extension().map_or(false, |ext| ext == "toml") {
+            toml::from_slice(&contents).map_err(|e| error::Error::Custom(Box::new(e)))
+        } else {
+            Ok(serde_json::from_slice(&contents)?)
+        }
+    }
+}
+
+impl<C: Borrow<str>, T: Borrow<str>> fmt::Debug for Glyph<C, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Glyph")
+            .field("consumer_key", &self.client.identifier())
+            .field("consumer_secret", &"<redacted>")
+            .field("access_key", &self.glyph.identifier())
+            .field("access_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(feature = "credentials")]
+#[derive(Serialize, Deserialize)]
+struct Repr<C, T> {
+    consumer_key: C,
+    consumer_secret: C,
+    #[serde(alias = "access_token")]
+    access_key: T,
+    #[serde(alias = "access_token_secret")]
+    access_secret: T,
+}
+
+#[cfg(feature = "credentials")]
+impl<C: Borrow<str>, T: Borrow<str>> Serialize for Glyph<C, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Repr {
+            consumer_key: self.client.identifier(),
+            consumer_secret: self.client.secret(),
+            access_key: self.glyph.identifier(),
+            access_secret: self.glyph.secret(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "credentials")]
+impl<'de, C, T> Deserialize<'de> for Glyph<C, T>
+where
+    C: Borrow<str> + Deserialize<'de>,
+    T: Borrow<str> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let r = Repr::<C, T>::deserialize(deserializer)?;
+        Ok(Glyph::new(
+            r.consumer_key,
+            r.consumer_secret,
+            r.access_key,
+            r.access_secret,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let glyph = Glyph::new("ck", "cs_secret", "ak", "as_secret");
+        let debug = format!("{:?}", glyph);
+        assert!(debug.contains("\"ck\"") && debug.contains("\"ak\""));
+        assert!(!debug.contains("secret\""));
+    }
+
+    #[cfg(feature = "credentials")]
+    #[test]
+    fn deserialize() {
+        let json = r#"{"consumer_key":"ck","consumer_secret":"cs","access_token":"ak","access_secret":"as"}"#;
+        let glyph: Glyph = serde_json::from_str(json).unwrap();
+        assert_eq!(glyph.glyph.identifier(), "ak");
+
+        let toml = "consumer_key = \"ck\"\nconsumer_secret = \"cs\"\naccess_key = \"ak\"\naccess_secret = \"as\"\n";
+        let glyph: Glyph = toml::from_str(toml).unwrap();
+        assert_eq!(glyph.client.secret(), "cs");
+        assert_eq!(serde_json::to_value(&glyph).unwrap()["access_secret"], "as");
+    }
+}
+
   /// Filter stream to only return Tweets containing given phrases.
     ///
     /// A phrase may be one or more terms separated by spaces, and a phrase will match if all
@@ -0,0 +1,237 @@
+//! Sentiment analysis in languages other than English.
+//!
+//! The tables bundled with VADER only cover English. A `LanguagePack` holds the tables of
+//! another language, and `Languages` maps the language identifiers Twitter assigns to Tweets
+//! (the `lang` field, e.g. "en", "es" or "pt") to the analyzer used for each language.
+//!
+//! `TwitterStream::with_languages` scores each Tweet with the analyzer of its language. Tweets
+//! in a language without an analyzer are not dropped silently: they are yielded along with an
+//! `UnsupportedLanguage` error. Use `StreamBuilder::language` to avoid receiving them in the
+//! first place.
+//!
+//! Only the tables of the analyzer are language-specific: the handling of all-caps words,
+//! punctuation, "least" and "kind of" follows the English rules of VADER.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use khipu::language::{LanguagePack, Languages};
+//!
+//! # fn main() -> Result<(), khipu::lexicon::LexiconError> {
+//! let spanish = LanguagePack::from_dir("lang/es")?;
+//!
+//! let mut languages = Languages::vader();
+//! languages.insert("es", spanish.analyzer());
+//!
+//! assert!(languages.get("es").is_some());
+//! assert!(languages.get("en-gb").is_some());
+//! assert!(languages.get("fr").is_none());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+use crate::lexicon::{EmojiLexicon, Lexicon, LexiconError, WordList};
+use crate::SentimentIntensityAnalyzer;
+
+/// The tables of a language, as described in the [`lexicon`](../lexicon/index.html) module.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LanguagePack {
+    pub lexicon: Lexicon,
+    pub emoji_lexicon: EmojiLexicon,
+    pub boosters: Lexicon,
+    pub negations: WordList,
+    pub contrastives: WordList,
+    pub idioms: Lexicon,
+}
+
+/// A set of analyzers keyed by language identifier.
+#[derive(Clone, Debug, Default)]
+pub struct Languages<'a> {
+    analyzers: HashMap<String, SentimentIntensityAnalyzer<'a>>,
+}
+
+/// An error returned for Tweets in a language without an analyzer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedLanguage {
+    /// The language of the Tweet, or `None` if Twitter did not detect any.
+    pub lang: Option<String>,
+}
+
+impl LanguagePack {
+    /// Creates a language pack with empty tables.
+    pub fn new() -> Self {
+        LanguagePack::default()
+    }
+
+    /// Returns a copy of the English tables bundled with VADER.
+    pub fn vader() -> Self {
+        LanguagePack {
+            lexicon: Lexicon::vader().clone(),
+            emoji_lexicon: EmojiLexicon::vader().clone(),
+            boosters: Lexicon::vader_boosters().clone(),
+            negations: WordList::vader_negations().clone(),
+            contrastives: WordList::vader_contrastives().clone(),
+            idioms: Lexicon::vader_idioms().clone(),
+        }
+    }
+
+    /// Reads a language pack from a directory.
+    ///
+    /// The directory must contain a `lexicon.txt` file, and may contain `emoji.txt`,
+    /// `boosters.txt` and `idioms.txt` lexicon files as well as `negations.txt` and
+    /// `contrastives.txt` word lists. Missing optional files result in empty tables.
+    pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, LexiconError> {
+        let path = path.as_ref();
+        let optional = |name: &str| {
+            let file = path.join(name);
+            if file.exists() {
+                Some(file)
+            } else {
+                None
+            }
+        };
+
+        let mut pack = LanguagePack::new();
+        pack.lexicon = Lexicon::from_file(path.join("lexicon.txt"))?;
+        if let Some(file) = optional("emoji.txt") {
+            pack.emoji_lexicon = EmojiLexicon::from_file(file)?;
+        }
+        if let Some(file) = optional("boosters.txt") {
+            pack.boosters = Lexicon::from_file(file)?;
+        }
+        if let Some(file) = optional("negations.txt") {
+            pack.negations = WordList::from_file(file)?;
+        }
+        if let Some(file) = optional("contrastives.txt") {
+            pack.contrastives = WordList::from_file(file)?;
+        }
+        if let Some(file) = optional("idioms.txt") {
+            pack.idioms = Lexicon::from_file(file)?;
+        }
+
+        Ok(pack)
+    }
+
+    /// Creates an analyzer borrowing the tables of this language pack.
+    pub fn analyzer(&self) -> SentimentIntensityAnalyzer<'_> {
+        let mut analyzer = SentimentIntensityAnalyzer::new();
+        analyzer
+            .lexicon(&self.lexicon)
+            .emoji_lexicon(&self.emoji_lexicon)
+            .boosters(&self.boosters)
+            .negations(&self.negations)
+            .contrastives(&self.contrastives)
+            .idioms(&self.idioms);
+        analyzer
+    }
+}
+
+impl<'a> Languages<'a> {
+    /// Creates an empty set of analyzers.
+    pub fn new() -> Self {
+        Languages::default()
+    }
+
+    /// Creates a set holding the VADER analyzer for English ("en").
+    pub fn vader() -> Languages<'static> {
+        let mut languages = Languages::new();
+        languages.insert("en", SentimentIntensityAnalyzer::new());
+        languages
+    }
+
+    /// Sets the analyzer of `lang`, returning the previous analyzer, if any.
+    ///
+    /// Language identifiers are case-insensitive.
+    pub fn insert(
+        &mut self,
+        lang: &str,
+        analyzer: SentimentIntensityAnalyzer<'a>,
+    ) -> Option<SentimentIntensityAnalyzer<'a>> {
+        self.analyzers.insert(lang.to_lowercase(), analyzer)
+    }
+
+    /// Removes the analyzer of `lang`, returning it, if any.
+    pub fn remove(&mut self, lang: &str) -> Option<SentimentIntensityAnalyzer<'a>> {
+        self.analyzers.remove(&lang.to_lowercase())
+    }
+
+    /// Returns the analyzer of `lang`, if any.
+    ///
+    /// If there is no analyzer for a regional identifier such as "en-gb", the analyzer of its
+    /// primary language ("en") is returned.
+    pub fn get(&self, lang: &str) -> Option<&SentimentIntensityAnalyzer<'a>> {
+        let lang = lang.to_lowercase();
+        self.analyzers.get(&lang).or_else(|| {
+            let primary = lang.split(|c| c == '-' || c == '_').next()?;
+            self.analyzers.get(primary)
+        })
+    }
+
+    /// Returns the analyzer of a Tweet with the given `lang` field.
+    pub fn for_tweet(
+        &self,
+        lang: Option<&str>,
+    ) -> Result<&SentimentIntensityAnalyzer<'a>, UnsupportedLanguage> {
+        lang.and_then(|lang| self.get(lang))
+            .ok_or_else(|| UnsupportedLanguage {
+                lang: lang.map(str::to_string),
+            })
+    }
+
+    /// Iterates over the supported language identifiers, in arbitrary order.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.analyzers.keys().map(String::as_str)
+    }
+}
+
+impl Display for UnsupportedLanguage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.lang {
+            Some(ref lang) => write!(f, "No sentiment analyzer for language: {}", lang),
+            None => f.write_str("No sentiment analyzer for Tweets without a language"),
+        }
+    }
+}
+
+impl error::Error for UnsupportedLanguage {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ptr;
+
+    #[test]
+    fn get() {
+        let mut languages = Languages::vader();
+        languages.insert(
+            "PT",
+            SentimentIntensityAnalyzer::from_lexicon(Lexicon::new()),
+        );
+
+        let en = languages.get("en").unwrap();
+        assert!(ptr::eq(languages.get("en-GB").unwrap(), en));
+        assert!(ptr::eq(languages.get("EN_gb").unwrap(), en));
+        assert!(!ptr::eq(languages.get("pt").unwrap(), en));
+        assert!(languages.get("fr").is_none());
+        assert!(languages.get("").is_none());
+    }
+
+    #[test]
+    fn for_tweet() {
+        let languages = Languages::vader();
+        assert!(languages.for_tweet(Some("en-gb")).is_ok());
+
+        let error = languages.for_tweet(Some("fr")).unwrap_err();
+        assert_eq!(error.lang.as_ref().map(String::as_str), Some("fr"));
+        assert_eq!(error.to_string(), "No sentiment analyzer for language: fr");
+
+        let error = languages.for_tweet(None).unwrap_err();
+        assert_eq!(error, UnsupportedLanguage { lang: None });
+    }
+}
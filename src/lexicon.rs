@@ -0,0 +1,462 @@
+//! Word lists used by `SentimentIntensityAnalyzer`.
+//!
+//! An analyzer is built from six tables:
+//!
+//! * a `Lexicon` mapping words to their valence, from -4.0 (most negative) to 4.0 (most
+//!   positive),
+//! * an `EmojiLexicon` mapping emoji to a textual description which is scored in their place,
+//! * a `Lexicon` of booster words ("very", "barely", ...) mapping to the amount they add to the
+//!   valence of the word they precede,
+//! * a `WordList` of negations ("not", "never", ...),
+//! * a `WordList` of contrastive conjunctions ("but"), which shift the emphasis of a sentence
+//!   to the words following them,
+//! * a `Lexicon` of idioms ("under the weather", ...) mapping to the valence which replaces the
+//!   valence of their words.
+//!
+//! The tables bundled with VADER are available through `Lexicon::vader`,
+//! `EmojiLexicon::vader`, `Lexicon::vader_boosters`, `WordList::vader_negations`,
+//! `WordList::vader_contrastives` and `Lexicon::vader_idioms`. Tables for other languages are
+//! bundled into a [`LanguagePack`](../language/struct.LanguagePack.html).
+//!
+//! Lexicon files use the format of the VADER lexicon: one entry per line, with the word and its
+//! value separated by a tab. Any further tab-separated columns are ignored, as are blank lines.
+//! The words of a `Lexicon` are kept as written, so that entries differing only by case, such
+//! as the emoticons `:D` and `:d`, stay distinct. `WordList`s ignore case.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use khipu::lexicon::Lexicon;
+//! use khipu::SentimentIntensityAnalyzer;
+//!
+//! # fn main() -> Result<(), khipu::lexicon::LexiconError> {
+//! // Start from the VADER lexicon,
+//! let mut lexicon = Lexicon::vader().clone();
+//! // add domain terms without touching the valence of words VADER already knows,
+//! lexicon.merge(Lexicon::from_file("finance.tsv")?);
+//! // and apply the overrides of this deployment.
+//! lexicon.overlay(Lexicon::from_file("overrides.tsv")?);
+//!
+//! let mut analyzer = SentimentIntensityAnalyzer::new();
+//! analyzer.lexicon(lexicon);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::collections::hash_map::{self, HashMap};
+use std::collections::HashSet;
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::num::ParseFloatError;
+use std::path::Path;
+
+/// A table mapping words to a numeric value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lexicon {
+    entries: HashMap<String, f64>,
+}
+
+/// A table mapping emoji to their textual description.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EmojiLexicon {
+    entries: HashMap<String, String>,
+}
+
+/// A set of words, compared case-insensitively.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WordList {
+    words: HashSet<String>,
+}
+
+/// An error that occurred while loading a lexicon.
+#[derive(Debug)]
+pub enum LexiconError {
+    /// The lexicon could not be read.
+    Io(io::Error),
+    /// A line has a word but no value. The enclosed value is the line number, starting at 1.
+    MissingValue(usize),
+    /// A line has a value which is not a number. The enclosed values are the line number,
+    /// starting at 1, and the error returned from the standard library.
+    InvalidValue(usize, ParseFloatError),
+}
+
+impl Lexicon {
+    pub fn new() -> Self {
+        Lexicon::default()
+    }
+
+    /// Returns the lexicon bundled with VADER.
+    pub fn vader() -> &'static Lexicon {
+        &crate::LEXICON
+    }
+
+    /// Returns the booster words bundled with VADER.
+    pub fn vader_boosters() -> &'static Lexicon {
+        &crate::BOOSTER_DICT
+    }
+
+    /// Returns the idioms bundled with VADER, such as "under the weather" or "the bomb".
+    pub fn vader_idioms() -> &'static Lexicon {
+        &crate::IDIOMS
+    }
+
+    /// Parses a lexicon from a string.
+    pub fn parse(raw: &str) -> Result<Self, LexiconError> {
+        Lexicon::from_reader(raw.as_bytes())
+    }
+
+    /// Reads a lexicon from a reader.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, LexiconError> {
+        let mut lexicon = Lexicon::new();
+        for_each_entry(reader, |line, word, value| {
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|e| LexiconError::InvalidValue(line, e))?;
+            lexicon.insert(word, value);
+            Ok(())
+        })?;
+        Ok(lexicon)
+    }
+
+    /// Reads a lexicon from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LexiconError> {
+        Lexicon::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the value of `word`, if present.
+    pub fn get(&self, word: &str) -> Option<f64> {
+        self.entries.get(word).cloned()
+    }
+
+    /// Returns whether `word` is present.
+    pub fn contains(&self, word: &str) -> bool {
+        self.entries.contains_key(word)
+    }
+
+    /// Sets the value of `word`, returning the previous value, if any.
+    pub fn insert(&mut self, word: impl Into<String>, value: f64) -> Option<f64> {
+        self.entries.insert(word.into(), value)
+    }
+
+    /// Removes `word`, returning its value, if any.
+    pub fn remove(&mut self, word: &str) -> Option<f64> {
+        self.entries.remove(word)
+    }
+
+    /// Adds the words of `other` which are not present in `self`.
+    /// Values already present in `self` are kept.
+    pub fn merge(&mut self, other: Lexicon) {
+        for (word, value) in other.entries {
+            self.entries.entry(word).or_insert(value);
+        }
+    }
+
+    /// Adds all the words of `other`, replacing the values already present in `self`.
+    pub fn overlay(&mut self, other: Lexicon) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over the words and their values, in arbitrary order.
+    pub fn iter(&self) -> hash_map::Iter<'_, String, f64> {
+        self.entries.iter()
+    }
+}
+
+impl EmojiLexicon {
+    pub fn new() -> Self {
+        EmojiLexicon::default()
+    }
+
+    /// Returns the emoji lexicon bundled with VADER.
+    pub fn vader() -> &'static EmojiLexicon {
+        &crate::EMOJI_LEXICON
+    }
+
+    /// Parses an emoji lexicon from a string.
+    pub fn parse(raw: &str) -> Result<Self, LexiconError> {
+        EmojiLexicon::from_reader(raw.as_bytes())
+    }
+
+    /// Reads an emoji lexicon from a reader.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, LexiconError> {
+        let mut lexicon = EmojiLexicon::new();
+        for_each_entry(reader, |_, emoji, description| {
+            lexicon.insert(emoji, description);
+            Ok(())
+        })?;
+        Ok(lexicon)
+    }
+
+    /// Reads an emoji lexicon from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LexiconError> {
+        EmojiLexicon::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns the description of `emoji`, if present.
+    pub fn get(&self, emoji: &str) -> Option<&str> {
+        self.entries.get(emoji).map(String::as_str)
+    }
+
+    /// Returns whether `emoji` is present.
+    pub fn contains(&self, emoji: &str) -> bool {
+        self.entries.contains_key(emoji)
+    }
+
+    /// Sets the description of `emoji`, returning the previous description, if any.
+    pub fn insert(&mut self, emoji: impl Into<String>, description: impl Into<String>) -> Option<String> {
+        self.entries.insert(emoji.into(), description.into())
+    }
+
+    /// Removes `emoji`, returning its description, if any.
+    pub fn remove(&mut self, emoji: &str) -> Option<String> {
+        self.entries.remove(emoji)
+    }
+
+    /// Adds the emoji of `other` which are not present in `self`.
+    /// Descriptions already present in `self` are kept.
+    pub fn merge(&mut self, other: EmojiLexicon) {
+        for (emoji, description) in other.entries {
+            self.entries.entry(emoji).or_insert(description);
+        }
+    }
+
+    /// Adds all the emoji of `other`, replacing the descriptions already present in `self`.
+    pub fn overlay(&mut self, other: EmojiLexicon) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl WordList {
+    pub fn new() -> Self {
+        WordList::default()
+    }
+
+    /// Returns the negations bundled with VADER.
+    pub fn vader_negations() -> &'static WordList {
+        &crate::NEGATIONS
+    }
+
+    /// Returns the contrastive conjunctions bundled with VADER, i.e. "but".
+    pub fn vader_contrastives() -> &'static WordList {
+        &crate::CONTRASTIVES
+    }
+
+    /// Reads a word list from a reader, one word per line. Blank lines are ignored.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, LexiconError> {
+        let mut list = WordList::new();
+        for line in reader.lines() {
+            let line = line?;
+            let word = line.trim();
+            if !word.is_empty() {
+                list.insert(word);
+            }
+        }
+        Ok(list)
+    }
+
+    /// Reads a word list from a file, one word per line. Blank lines are ignored.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LexiconError> {
+        WordList::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Returns whether `word` is present, ignoring case.
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+
+    /// Adds `word` in lowercase, returning whether it was not present yet.
+    pub fn insert(&mut self, word: impl Into<String>) -> bool {
+        self.words.insert(word.into().to_lowercase())
+    }
+
+    /// Removes `word`, ignoring case, returning whether it was present.
+    pub fn remove(&mut self, word: &str) -> bool {
+        self.words.remove(&word.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl<'a> From<&'a HashMap<&'a str, f64>> for Lexicon {
+    fn from(map: &'a HashMap<&'a str, f64>) -> Self {
+        Lexicon {
+            entries: map.iter().map(|(&k, &v)| (k.to_string(), v)).collect(),
+        }
+    }
+}
+
+impl<S: Into<String>> std::iter::FromIterator<S> for WordList {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        WordList {
+            words: iter
+                .into_iter()
+                .map(|word| word.into().to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+macro_rules! into_cow {
+    ($($T:ty),*) => {$(
+        impl<'a> From<$T> for Cow<'a, $T> {
+            fn from(t: $T) -> Self {
+                Cow::Owned(t)
+            }
+        }
+
+        impl<'a> From<&'a $T> for Cow<'a, $T> {
+            fn from(t: &'a $T) -> Self {
+                Cow::Borrowed(t)
+            }
+        }
+    )*};
+}
+
+into_cow!(Lexicon, EmojiLexicon, WordList);
+
+impl Display for LexiconError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            LexiconError::Io(ref e) => write!(f, "Error reading lexicon: {}", e),
+            LexiconError::MissingValue(line) => write!(f, "Missing value on line {}", line),
+            LexiconError::InvalidValue(line, ref e) => {
+                write!(f, "Invalid value on line {}: {}", line, e)
+            }
+        }
+    }
+}
+
+impl error::Error for LexiconError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            LexiconError::Io(ref e) => Some(e),
+            LexiconError::MissingValue(_) => None,
+            LexiconError::InvalidValue(_, ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for LexiconError {
+    fn from(err: io::Error) -> Self {
+        LexiconError::Io(err)
+    }
+}
+
+/// Calls `f` with the line number, the word and the value of each non-blank line.
+fn for_each_entry<R, F>(reader: R, mut f: F) -> Result<(), LexiconError>
+where
+    R: BufRead,
+    F: FnMut(usize, &str, &str) -> Result<(), LexiconError>,
+{
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut columns = line.split('\t');
+        let word = columns.next().unwrap_or_default();
+        match columns.next() {
+            Some(value) if !word.is_empty() && !value.trim().is_empty() => f(i + 1, word, value)?,
+            _ => return Err(LexiconError::MissingValue(i + 1)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let lexicon =
+            Lexicon::parse("good\t1.9\t0.7\t[2, 2, 1]\r\n\r\n:D\t2.3\r\n:d\t1.2\r\n").unwrap();
+        assert_eq!(lexicon.len(), 3);
+        assert_eq!(lexicon.get("good"), Some(1.9));
+        assert_eq!(lexicon.get(":D"), Some(2.3));
+        assert_eq!(lexicon.get(":d"), Some(1.2));
+
+        let emoji = EmojiLexicon::parse("\u{1f600}\tgrinning face\r\n").unwrap();
+        assert_eq!(emoji.get("\u{1f600}"), Some("grinning face"));
+    }
+
+    #[test]
+    fn word_list() {
+        let mut list = WordList::from_reader(&b"Not\n\nnever\r\n"[..]).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list.contains("not"));
+        assert!(list.contains("NOT"));
+        assert!(list.contains("Never"));
+        assert!(!list.insert("NEVER"));
+        assert!(list.remove("nOt"));
+        assert!(!list.contains("not"));
+
+        let list: WordList = vec!["But", "Yet"].into_iter().collect();
+        assert!(list.contains("but"));
+        assert!(list.contains("yet"));
+    }
+
+    #[test]
+    fn errors() {
+        match Lexicon::parse("good\t1.9\nbad\n") {
+            Err(LexiconError::MissingValue(line)) => assert_eq!(line, 2),
+            r => panic!("expected a missing value, got {:?}", r),
+        }
+        match Lexicon::parse("good\t1.9\n\tbad\n") {
+            Err(LexiconError::MissingValue(line)) => assert_eq!(line, 2),
+            r => panic!("expected a missing value, got {:?}", r),
+        }
+        // Blank lines count toward the line numbers.
+        match Lexicon::parse("good\t1.9\n\n  \nbad\tworse\n") {
+            Err(LexiconError::InvalidValue(line, _)) => assert_eq!(line, 4),
+            r => panic!("expected an invalid value, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn merge_overlay() {
+        let base = Lexicon::parse("good\t1.9\nbad\t-2.5\n").unwrap();
+        let other = Lexicon::parse("good\t3.0\nbullish\t2.0\n").unwrap();
+
+        let mut merged = base.clone();
+        merged.merge(other.clone());
+        assert_eq!(merged.get("good"), Some(1.9));
+        assert_eq!(merged.get("bullish"), Some(2.0));
+        assert_eq!(merged.len(), 3);
+
+        let mut overlaid = base;
+        overlaid.overlay(other);
+        assert_eq!(overlaid.get("good"), Some(3.0));
+        assert_eq!(overlaid.get("bad"), Some(-2.5));
+        assert_eq!(overlaid.len(), 3);
+    }
+}
@@ -7,20 +7,40 @@ extern crate unicode_normalization;
 mod regexbuilder;
 mod util;
 
+pub mod alert;
+pub mod auth;
+pub mod document;
 pub mod error;
+pub mod explain;
+pub mod lexicon;
+pub mod managed;
+pub mod matcher;
 #[cfg(feature = "runtime")]
 pub mod rt;
 pub mod filters;
-
-mod Brotli;
+pub mod language;
+pub mod message;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod monitor;
+pub mod rate_limit;
+pub mod reconnect;
+pub mod sentiment;
+pub mod v2;
+pub mod window;
+
+mod decode;
 mod glyph;
 
 pub use oauth::Credentials;
 
 pub use crate::error::Error;
 pub use crate::glyph::Glyph;
+pub use crate::message::StreamMessage;
+pub use crate::sentiment::SentimentScores;
 
-use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::future::Future;
 use std::marker::Unpin;
 use std::pin::Pin;
@@ -30,19 +50,30 @@ use std::time::Duration;
 
 use bytes::Bytes;
 use futures_core::Stream;
+use futures_util::try_future::{MapOk, TryFutureExt};
 use futures_util::{ready, FutureExt, StreamExt};
 use http::response::Parts;
 use hyper::body::{Body, Payload};
 use hyper::client::connect::Connect;
 use hyper::client::{Client, ResponseFuture};
 use hyper::header::{
-    HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+    HeaderMap, HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE,
 };
-use hyper::Request;
+use hyper::{Request, StatusCode};
 use string::TryFrom;
 
-use crate::Brotli::MaybeBrotli;
-use crate::filters::{FilterLevel, RequestMethod, StatusCode, Uri};
+use crate::auth::Authenticate;
+use crate::decode::{Decoder, Encoding, ACCEPTED_ENCODINGS};
+use crate::filters::{FilterLevel, FilterParams, RequestMethod, Uri};
+use crate::explain::{record, Adjustment, Explanation, GlyphTrace};
+use crate::language::Languages;
+use crate::lexicon::{EmojiLexicon, Lexicon, WordList};
+use crate::managed::ManagedStream;
+use crate::message::Messages;
+use crate::rate_limit::RateLimitStatus;
+#[cfg(feature = "runtime")]
+use crate::reconnect::{ReconnectPolicy, ReconnectingStream, TokioClock};
+use crate::sentiment::{LanguageStream, SentimentStream};
 use crate::util::*;
 
 
@@ -105,7 +136,7 @@ static RAW_LEXICON: &'static str = include_str!("resources/vader_lexicon.txt");
 static RAW_EMOJI_LEXICON: &'static str = include_str!("resources/emoji_utf8_lexicon.txt");
 
 lazy_static! {
-    static ref BOOSTER_DICT: HashMap<&'static str, f64> =  hashmap![
+    static ref BOOSTER_DICT: Lexicon = Lexicon::from(&hashmap![
          "absolutely" => B_INCR, "amazingly" => B_INCR, "awfully" => B_INCR, "completely" => B_INCR, "considerably" => B_INCR,
          "decidedly" => B_INCR, "deeply" => B_INCR, "effing" => B_INCR, "enormously" => B_INCR,
          "entirely" => B_INCR, "especially" => B_INCR, "exceptionally" => B_INCR, "extremely" => B_INCR,
@@ -122,33 +153,37 @@ lazy_static! {
          "kind of" => B_DECR, "kinda" => B_DECR, "kindof" => B_DECR, "kind-of" => B_DECR,
          "less" => B_DECR, "little" => B_DECR, "marginally" => B_DECR, "occasionally" => B_DECR, "partly" => B_DECR,
          "scarcely" => B_DECR, "slightly" => B_DECR, "somewhat" => B_DECR,
-         "sort of" => B_DECR, "sorta" => B_DECR, "sortof" => B_DECR, "sort-of" => B_DECR];
+         "sort of" => B_DECR, "sorta" => B_DECR, "sortof" => B_DECR, "sort-of" => B_DECR]);
 
-    /**
-     * These dicts were used in some WIP or planned features in the original
-     * I may implement them later if I can understand how they're intended to work
-     **/
+    static ref NEGATIONS: WordList = NEGATION_glyphS.iter().cloned().collect();
 
-    // // check for sentiment laden idioms that do not contain lexicon words (future work, not yet implemented)
-    // static ref SENTIMENT_LADEN_IDIOMS: HashMap<&'static str, f64> = hashmap![
-    //      "cut the mustard" => 2.0, "hand to mouth" => -2.0,
-    //      "back handed" => -2.0, "blow smoke" => -2.0, "blowing smoke" => -2.0,
-    //      "upper hand" => 1.0, "break a leg" => 2.0,
-    //      "cooking with gas" => 2.0, "in the black" => 2.0, "in the red" => -2.0,
-    //      "on the ball" => 2.0, "under the weather" => -2.0];
+    static ref CONTRASTIVES: WordList = ["but"].iter().cloned().collect();
 
+    // check for sentiment laden idioms that do not contain lexicon words
+    static ref SENTIMENT_LADEN_IDIOMS: HashMap<&'static str, f64> = hashmap![
+         "cut the mustard" => 2.0, "hand to mouth" => -2.0,
+         "back handed" => -2.0, "blow smoke" => -2.0, "blowing smoke" => -2.0,
+         "upper hand" => 1.0, "break a leg" => 2.0,
+         "cooking with gas" => 2.0, "in the black" => 2.0, "in the red" => -2.0,
+         "on the ball" => 2.0, "under the weather" => -2.0];
 
     // check for special case idioms containing lexicon words
     static ref SPECIAL_CASE_IDIOMS: HashMap<&'static str, f64> = hashmap![
          "the shit" => 3.0, "the bomb" => 3.0, "bad ass" => 1.5, "yeah right" => -2.0,
          "kiss of death" => -1.5];
 
+    static ref IDIOMS: Lexicon = {
+        let mut idioms = Lexicon::from(&*SENTIMENT_LADEN_IDIOMS);
+        idioms.overlay(Lexicon::from(&*SPECIAL_CASE_IDIOMS));
+        idioms
+    };
+
     static ref ALL_CAPS_RE: Regex = Regex::new(r"^[A-Z\W]+$").unwrap();
 
     static ref PUNCTUATION: &'static str = "[!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~]";
 
-    pub static ref LEXICON: HashMap<&'static str, f64> = parse_raw_lexicon(RAW_LEXICON);
-    pub static ref EMOJI_LEXICON: HashMap<&'static str, &'static str> = parse_raw_emoji_lexicon(RAW_EMOJI_LEXICON);
+    pub static ref LEXICON: Lexicon = Lexicon::parse(RAW_LEXICON).unwrap();
+    pub static ref EMOJI_LEXICON: EmojiLexicon = EmojiLexicon::parse(RAW_EMOJI_LEXICON).unwrap();
 }
 
 
@@ -863,30 +898,6 @@ pub fn character_count(text: &str, http_url_len: i32, https_url_len: i32) -> usi
     len
 }
 
-pub fn parse_raw_lexicon(raw_lexicon: &str) -> HashMap<&str, f64> {
-    let lines = raw_lexicon.split("\n");
-    let mut lex_dict = HashMap::new();
-    for line in lines {
-        let mut split_line = line.split('\t');
-        let word = split_line.next().unwrap();
-        let val  = split_line.next().unwrap();
-        lex_dict.insert(word, val.parse().unwrap());
-    }
-    lex_dict
-}
-
-pub fn parse_raw_emoji_lexicon(raw_emoji_lexicon: &str) -> HashMap<&str, &str> {
-    let lines = raw_emoji_lexicon.split("\n");
-    let mut emoji_dict = HashMap::new();
-    for line in lines {
-        let mut split_line = line.split('\t');
-        let word = split_line.next().unwrap();
-        let desc  = split_line.next().unwrap();
-        emoji_dict.insert(word, desc);
-    }
-    emoji_dict
-}
-
 /**
  *  Stores glyphs and useful info about text
  **/
@@ -962,9 +973,9 @@ fn is_all_caps(glyph: &str) -> bool {
     ALL_CAPS_RE.is_match(glyph) && glyph.len() > 1
 }
 
-//Checks if glyph is in the list of NEGATION_SCALAR
-fn is_negated(glyph: &str) -> bool {
-    if NEGATION_glyphS.contains(&glyph.to_lowercase().as_str()) {
+//Checks if glyph is in the list of negations
+fn is_negated(glyph: &str, negations: &WordList) -> bool {
+    if negations.contains(glyph) {
         return true;
     }
     glyph.contains("n't")
@@ -982,11 +993,11 @@ fn normalize_score(score: f64) -> f64 {
 }
 
 //Checks how previous glyphs affect the valence of the current glyph
-fn scalar_inc_dec(glyph: &str, valence: f64, has_mixed_caps: bool) -> f64 {
+fn scalar_inc_dec(glyph: &str, valence: f64, has_mixed_caps: bool, boosters: &Lexicon) -> f64 {
     let mut scalar = 0.0;
     let glyph_lower: &str = &glyph.to_lowercase();
-    if BOOSTER_DICT.contains_key(glyph_lower) {
-        scalar = *BOOSTER_DICT.get(glyph_lower).unwrap();
+    if let Some(booster) = boosters.get(glyph_lower) {
+        scalar = booster;
         if valence < 0.0 {
             scalar *= -1.0;
         }
@@ -1015,28 +1026,76 @@ fn sum_sentiment_scores(scores: Vec<f64>) -> (f64, f64, u32) {
     (pos_sum, neg_sum, neu_count)
 }
 
+#[derive(Clone, Debug)]
 pub struct SentimentIntensityAnalyzer<'a> {
-    lexicon: &'a HashMap<&'a str, f64>,
-    emoji_lexicon: &'a HashMap<&'a str, &'a str>,
+    lexicon: Cow<'a, Lexicon>,
+    emoji_lexicon: Cow<'a, EmojiLexicon>,
+    boosters: Cow<'a, Lexicon>,
+    negations: Cow<'a, WordList>,
+    contrastives: Cow<'a, WordList>,
+    idioms: Cow<'a, Lexicon>,
 }
 
 impl<'a> SentimentIntensityAnalyzer<'a> {
+    /// Creates an analyzer using the tables bundled with VADER.
     pub fn new() -> SentimentIntensityAnalyzer<'static>{
         SentimentIntensityAnalyzer {
-            lexicon: &LEXICON,
-            emoji_lexicon: &EMOJI_LEXICON,
+            lexicon: Cow::Borrowed(Lexicon::vader()),
+            emoji_lexicon: Cow::Borrowed(EmojiLexicon::vader()),
+            boosters: Cow::Borrowed(Lexicon::vader_boosters()),
+            negations: Cow::Borrowed(WordList::vader_negations()),
+            contrastives: Cow::Borrowed(WordList::vader_contrastives()),
+            idioms: Cow::Borrowed(Lexicon::vader_idioms()),
         }
     }
 
-    pub fn from_lexicon<'b>(_lexicon: &'b HashMap<&str, f64>) ->
-    SentimentIntensityAnalyzer<'b> {
-        SentimentIntensityAnalyzer {
-            lexicon: _lexicon,
-            emoji_lexicon: &EMOJI_LEXICON,
-        }
+    /// Creates an analyzer using `lexicon` and the other tables bundled with VADER.
+    pub fn from_lexicon(lexicon: impl Into<Cow<'a, Lexicon>>) -> Self {
+        let mut analyzer: SentimentIntensityAnalyzer<'a> = SentimentIntensityAnalyzer::new();
+        analyzer.lexicon(lexicon);
+        analyzer
     }
 
-    fn get_total_sentiment(&self, sentiments: Vec<f64>, punct_emph_amplifier: f64) -> HashMap<&str, f64> {
+    /// Reset the lexicon mapping words to their valence.
+    pub fn lexicon(&mut self, lexicon: impl Into<Cow<'a, Lexicon>>) -> &mut Self {
+        self.lexicon = lexicon.into();
+        self
+    }
+
+    /// Reset the lexicon mapping emoji to their description.
+    pub fn emoji_lexicon(&mut self, emoji_lexicon: impl Into<Cow<'a, EmojiLexicon>>) -> &mut Self {
+        self.emoji_lexicon = emoji_lexicon.into();
+        self
+    }
+
+    /// Reset the booster words and the amount they add to the valence of the following words.
+    pub fn boosters(&mut self, boosters: impl Into<Cow<'a, Lexicon>>) -> &mut Self {
+        self.boosters = boosters.into();
+        self
+    }
+
+    /// Reset the words negating the valence of the following words.
+    pub fn negations(&mut self, negations: impl Into<Cow<'a, WordList>>) -> &mut Self {
+        self.negations = negations.into();
+        self
+    }
+
+    /// Reset the contrastive conjunctions ("but"), which de-emphasize the words before them and
+    /// emphasize the words after them.
+    pub fn contrastives(&mut self, contrastives: impl Into<Cow<'a, WordList>>) -> &mut Self {
+        self.contrastives = contrastives.into();
+        self
+    }
+
+    /// Reset the idioms whose valence replaces the valence of their words.
+    ///
+    /// Idioms are lowercase phrases of two or more words separated by single spaces.
+    pub fn idioms(&mut self, idioms: impl Into<Cow<'a, Lexicon>>) -> &mut Self {
+        self.idioms = idioms.into();
+        self
+    }
+
+    fn get_total_sentiment(&self, sentiments: Vec<f64>, punct_emph_amplifier: f64) -> SentimentScores {
         let (mut neg, mut neu, mut pos, mut compound) = (0f64, 0f64, 0f64, 0f64);
         if sentiments.len() > 0 {
             let mut total_sentiment: f64 = sentiments.iter().sum();
@@ -1060,31 +1119,58 @@ impl<'a> SentimentIntensityAnalyzer<'a> {
             neg = (neg_sum / total).abs();
             neu = (neu_count as f64 / total).abs();
         }
-        let sentiment_dict = hashmap!["neg" => neg,
-                                      "neu" => neu,
-                                      "pos" => pos,
-                                      "compound" => compound];
-        sentiment_dict
+        SentimentScores { neg, neu, pos, compound }
     }
 
-    pub fn polarity_scores(&self, text: &str) -> HashMap<&str, f64>{
+    pub fn polarity_scores(&self, text: &str) -> SentimentScores {
+        self.score_text(text, None)
+    }
+
+    /// Same as `polarity_scores`, but also returns the valence of each glyph and the
+    /// heuristics that adjusted it. No output is printed either way.
+    pub fn polarity_scores_explained(&self, text: &str) -> Explanation {
+        let mut explanation = Explanation::default();
+        let scores = self.score_text(text, Some(&mut explanation));
+        explanation.scores = scores;
+        explanation
+    }
+
+    fn score_text(&self, text: &str, mut explanation: Option<&mut Explanation>) -> SentimentScores {
         let text = self.append_emoji_descriptions(text);
         let parsedtext = ParsedText::from_text(&text);
-        println!("{:#?}", parsedtext.glyphs);
         let mut sentiments = Vec::new();
         let glyphs = &parsedtext.glyphs;
 
         for (i, word) in glyphs.iter().enumerate() {
-            if BOOSTER_DICT.contains_key(word.to_lowercase().as_str()) {
+            let mut trace = explanation.as_ref().map(|_| GlyphTrace::new(word));
+            if self.boosters.contains(&word.to_lowercase()) {
                 sentiments.push(0f64);
             } else if i < glyphs.len() - 1 && word.to_lowercase() == "kind"
                 && glyphs[i + 1].to_lowercase() == "of" {
                 sentiments.push(0f64);
             } else {
-                sentiments.push(self.sentiment_valence(&parsedtext, word, i));
+                sentiments.push(self.sentiment_valence(&parsedtext, word, i, trace.as_mut()));
+            }
+            if let (Some(e), Some(t)) = (explanation.as_mut(), trace) {
+                e.glyphs.push(t);
             }
         }
-        but_check(&glyphs, &mut sentiments);
+        self.idioms_check(&glyphs, &mut sentiments, explanation.as_mut().map(|e| &mut e.glyphs));
+
+        if let Some(e) = explanation {
+            let unscaled = sentiments.clone();
+            but_check(&glyphs, &mut sentiments, &self.contrastives);
+            for (t, (before, after)) in e.glyphs.iter_mut().zip(unscaled.iter().zip(&sentiments)) {
+                if after != before {
+                    t.adjustments.push(Adjustment::But { factor: after / before });
+                }
+                t.valence = *after;
+            }
+            e.punctuation_amplifier = parsedtext.punc_amplifier;
+            e.text = text.clone();
+        } else {
+            but_check(&glyphs, &mut sentiments, &self.contrastives);
+        }
         self.get_total_sentiment(sentiments, parsedtext.punc_amplifier)
     }
 
@@ -1093,64 +1179,125 @@ impl<'a> SentimentIntensityAnalyzer<'a> {
         let mut result = String::new();
         let mut prev_space = true;
         for chr in text.chars() {
-            if self.emoji_lexicon.contains_key(chr.to_string().as_str()) {
+            if let Some(description) = self.emoji_lexicon.get(chr.to_string().as_str()) {
                 if !prev_space {
                     result.push(' ');
                 }
-                result.push_str(self.emoji_lexicon.get(&chr.to_string().as_str()).unwrap());
+                result.push_str(description);
                 prev_space = false;
             } else {
                 prev_space = chr == ' ';
                 result.push(chr);
             }
         }
-        println!("{}", result);
         result
     }
 
-    fn sentiment_valence(&self, parsed: &ParsedText, word: &str, i: usize) -> f64 {
+    fn sentiment_valence(&self, parsed: &ParsedText, word: &str, i: usize,
+                         mut trace: Option<&mut GlyphTrace>) -> f64 {
         let mut valence = 0f64;
         let word_lower = word.to_lowercase();
         let glyphs = &parsed.glyphs;
-        if self.lexicon.contains_key(word_lower.as_str()) {
-            valence = *self.lexicon.get(word_lower.as_str()).unwrap();
+        if self.lexicon.contains(word_lower.as_str()) {
+            valence = self.lexicon.get(word_lower.as_str()).unwrap();
+            if let Some(ref mut t) = trace {
+                t.lexicon_valence = Some(valence);
+            }
             if is_all_caps(word) && parsed.has_mixed_caps {
                 if valence > 0f64 {
                     valence += C_INCR;
+                    record(&mut trace, || Adjustment::Caps { delta: C_INCR });
                 } else {
-                    valence -= C_INCR
+                    valence -= C_INCR;
+                    record(&mut trace, || Adjustment::Caps { delta: -C_INCR });
                 }
             }
             for start_i in 0..3 {
-                if i > start_i && !self.lexicon.contains_key(
+                if i > start_i && !self.lexicon.contains(
                     glyphs[i - start_i - 1].to_lowercase().as_str()) {
-                    let mut s = scalar_inc_dec(glyphs[i - start_i - 1], valence, parsed.has_mixed_caps);
+                    let prev = glyphs[i - start_i - 1];
+                    let mut s = scalar_inc_dec(prev, valence, parsed.has_mixed_caps, &self.boosters);
                     if start_i == 1 {
                         s *= 0.95;
                     } else if start_i == 2 {
                         s *= 0.9
                     }
                     valence += s;
-                    valence = negation_check(valence, glyphs, start_i, i);
+                    if s != 0f64 {
+                        record(&mut trace, || Adjustment::Booster {
+                            glyph: prev.to_string(), distance: start_i + 1, delta: s,
+                        });
+                    }
+                    let before = valence;
+                    valence = negation_check(valence, glyphs, start_i, i, &self.negations);
+                    if valence != before {
+                        record(&mut trace, || Adjustment::Negation {
+                            glyph: prev.to_string(), distance: start_i + 1, factor: valence / before,
+                        });
+                    }
                     if start_i == 2 {
-                        valence = special_idioms_check(valence, glyphs, i);
+                        for (n_gram, distance, b) in ngram_boosters(glyphs, i, &self.boosters) {
+                            valence += b;
+                            record(&mut trace, || Adjustment::Booster {
+                                glyph: n_gram, distance, delta: b,
+                            });
+                        }
                     }
                 }
             }
+            let before = valence;
             valence = least_check(valence, glyphs, i);
+            if valence != before {
+                record(&mut trace, || Adjustment::Least { factor: valence / before });
+            }
         }
         valence
     }
+
+    // Replaces the valence of the glyphs of each idiom ("under the weather", "the bomb", ...)
+    // with the valence of the idiom, carried by its last glyph. Longer idioms win.
+    fn idioms_check(&self, glyphs: &Vec<&str>, sentiments: &mut Vec<f64>,
+                    mut traces: Option<&mut Vec<GlyphTrace>>) {
+        let max_len = self.idioms.iter().map(|(idiom, _)| idiom.split(' ').count()).max().unwrap_or(0);
+        let glyphs: Vec<String> = glyphs.iter().map(|s| s.to_lowercase()).collect();
+        let mut start = 0;
+        while start < glyphs.len() {
+            let mut matched = 1;
+            for len in (2..=max_len.min(glyphs.len() - start)).rev() {
+                let idiom = glyphs[start..start + len].join(" ");
+                let mut valence = continue_opt!(self.idioms.get(&idiom));
+                if start > 0 && is_negated(&glyphs[start - 1], &self.negations) {
+                    valence *= NEGATION_SCALAR;
+                }
+                let last = start + len - 1;
+                for j in start..=last {
+                    let new = if j == last { valence } else { 0f64 };
+                    if let Some(ref mut traces) = traces {
+                        if new != sentiments[j] {
+                            traces[j].adjustments.push(Adjustment::Idiom {
+                                idiom: idiom.clone(), delta: new - sentiments[j],
+                            });
+                        }
+                    }
+                    sentiments[j] = new;
+                }
+                matched = len;
+                break;
+            }
+            start += matched;
+        }
+    }
 }
 
 /**
  * Check for specific patterns or glyphs, and modify sentiment as needed
  **/
-fn negation_check(valence: f64, glyphs: &Vec<&str>, start_i: usize, i: usize) -> f64 {
+fn negation_check(valence: f64, glyphs: &Vec<&str>, start_i: usize, i: usize,
+                  negations: &WordList) -> f64 {
     let mut valence = valence;
     let glyphs: Vec<String> = glyphs.iter().map(|s| s.to_lowercase()).collect();
     if start_i == 0 {
-        if is_negated(&glyphs[i - start_i - 1]) {
+        if is_negated(&glyphs[i - start_i - 1], negations) {
             valence *= NEGATION_SCALAR;
         }
     } else if start_i == 1 {
@@ -1160,7 +1307,7 @@ fn negation_check(valence: f64, glyphs: &Vec<&str>, start_i: usize, i: usize) ->
             valence *= 1.25
         } else if glyphs[i - 2] == "without" && glyphs[i - 1] == "doubt" {
             valence *= 1.0
-        } else if is_negated(&glyphs[i - start_i - 1]) {
+        } else if is_negated(&glyphs[i - start_i - 1], negations) {
             valence *= NEGATION_SCALAR;
         }
     } else if start_i == 2 {
@@ -1172,17 +1319,30 @@ fn negation_check(valence: f64, glyphs: &Vec<&str>, start_i: usize, i: usize) ->
             glyphs[i - 2] == "doubt" ||
             glyphs[i - 1] == "doubt" {
             valence *= 1.0;
-        } else if is_negated(&glyphs[i - start_i - 1]) {
+        } else if is_negated(&glyphs[i - start_i - 1], negations) {
             valence *= NEGATION_SCALAR;
         }
     }
     valence
 }
 
-// If "but" is in the glyphs, scales down the sentiment of words before "but" and
-// adds more emphasis to the words after
-fn but_check(glyphs: &Vec<&str>, sentiments: &mut Vec<f64>) {
-    match glyphs.iter().position(|&s| s.to_lowercase() == "but") {
+//Checks for multi-word boosters such as "kind of" in the three glyphs before the glyph
+fn ngram_boosters(glyphs: &Vec<&str>, i: usize, boosters: &Lexicon) -> Vec<(String, usize, f64)> {
+    let glyphs: Vec<String> = glyphs[i - 3..i].iter().map(|s| s.to_lowercase()).collect();
+    let n_grams = [
+        (format!("{} {} {}", glyphs[0], glyphs[1], glyphs[2]), 3),
+        (format!("{} {}", glyphs[0], glyphs[1]), 3),
+        (format!("{} {}", glyphs[1], glyphs[2]), 2),
+    ];
+    n_grams.iter()
+        .filter_map(|&(ref n_gram, distance)| boosters.get(n_gram).map(|b| (n_gram.clone(), distance, b)))
+        .collect()
+}
+
+// If a contrastive conjunction ("but") is in the glyphs, scales down the sentiment of words
+// before it and adds more emphasis to the words after
+fn but_check(glyphs: &Vec<&str>, sentiments: &mut Vec<f64>, contrastives: &WordList) {
+    match glyphs.iter().position(|&s| contrastives.contains(s)) {
         Some(but_index) => {
             for i in 0..sentiments.len() {
                 if i < but_index {
@@ -1276,19 +1436,28 @@ pub struct StreamBuilder<'a, T = Glyph> {
 /// which resolves to a `TwitterStream`.
 pub struct FutureTwitterStream {
     response: MaybeTimeout<ResponseFuture>,
+    error: Option<ErrorResponse>,
+}
+
+/// An unsuccessful response whose body is being read for the details of the error.
+struct ErrorResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: ReadPrefix<Decoder<MaybeTimeoutStream<Body>>>,
 }
 
 /// A listener for Twitter Streaming API.
 /// It yields JSON strings returned from the API.
 pub struct TwitterStream {
-    inner: Lines<MaybeBrotli<MaybeTimeoutStream<Body>>>,
+    inner: Lines<Decoder<MaybeTimeoutStream<Body>>>,
+    rate_limit: RateLimitStatus,
 }
 
 #[derive(Clone, Debug, oauth::Authorize)]
 struct BuilderInner<'a> {
     #[oauth1(skip)]
     #[cfg(feature = "runtime")]
-    timeout: Option<Duration>,
+    timeouts: Timeouts,
     #[oauth1(skip_if = "not")]
     stall_warnings: bool,
     filter_level: Option<FilterLevel>,
@@ -1303,17 +1472,13 @@ struct BuilderInner<'a> {
     count: Option<i32>,
 }
 
-impl<'a, C, A> StreamBuilder<'a, Glyph<C, A>>
-where
-    C: Borrow<str>,
-    A: Borrow<str>,
-{
+impl<'a, T: Authenticate> StreamBuilder<'a, T> {
     /// Create a streamBuilder for `POST statuses/filter` endpoint.
     ///
     /// See the [Twitter Developer Documentation][1] for more information.
     ///
     /// [1]: https://dev.twitter.com/streaming/reference/post/statuses/filter
-    pub fn filter(glyph: Glyph<C, A>) -> Self {
+    pub fn filter(glyph: T) -> Self {
         const URI: &str = "https://stream.twitter.com/1.1/statuses/filter.json";
         Self::custom(RequestMethod::POST, Uri::from_static(URI), glyph)
     }
@@ -1323,20 +1488,20 @@ where
     /// See the [Twitter Developer Documentation][1] for more information.
     ///
     /// [1]: https://dev.twitter.com/streaming/reference/get/statuses/sample
-    pub fn sample(glyph: Glyph<C, A>) -> Self {
+    pub fn sample(glyph: T) -> Self {
         const URI: &str = "https://stream.twitter.com/1.1/statuses/sample.json";
         Self::custom(RequestMethod::GET, Uri::from_static(URI), glyph)
     }
 
     /// Constructs a streamBuilder for a Stream at a custom endpoint.
-    pub fn custom(method: RequestMethod, endpoint: Uri, glyph: Glyph<C, A>) -> Self {
+    pub fn custom(method: RequestMethod, endpoint: Uri, glyph: T) -> Self {
         Self {
             method,
             endpoint,
             glyph,
             inner: BuilderInner {
                 #[cfg(feature = "runtime")]
-                timeout: Some(Duration::from_secs(90)),
+                timeouts: Timeouts::default(),
                 stall_warnings: false,
                 filter_level: None,
                 language: None,
@@ -1367,15 +1532,13 @@ where
     {
         let mut req = Request::streamBuilder();
         req.method(self.method.clone())
-            .header(ACCEPT_ENCODING, HeaderValue::from_static("Brotli"));
+            .header(ACCEPT_ENCODING, HeaderValue::from_static(ACCEPTED_ENCODINGS));
 
-        let mut oauth = oauth::StreamBuilder::new(self.glyph.client.as_ref(), oauth::HmacSha1);
-        oauth.glyph(self.glyph.glyph.as_ref());
         let req = if RequestMethod::POST == self.method {
             let oauth::Request {
                 authorization,
                 data,
-            } = oauth.post_form(&self.endpoint, &self.inner);
+            } = self.glyph.authorize_form(&self.endpoint, &self.inner);
 
             req.uri(self.endpoint.clone())
                 .header(AUTHORIZATION, Bytes::from(authorization))
@@ -1390,7 +1553,9 @@ where
             let oauth::Request {
                 authorization,
                 data: uri,
-            } = oauth.build(self.method.as_ref(), &self.endpoint, &self.inner);
+            } = self
+                .glyph
+                .authorize(self.method.as_ref(), &self.endpoint, &self.inner);
 
             req.uri(uri)
                 .header(AUTHORIZATION, Bytes::from(authorization))
@@ -1401,14 +1566,76 @@ where
         let res = client.request(req);
         FutureTwitterStream {
             #[cfg(feature = "runtime")]
-            response: timeout(res, self.inner.timeout),
+            response: timeout(res, self.inner.timeouts),
             #[cfg(not(feature = "runtime"))]
             response: timeout(res),
+            error: None,
         }
     }
+
+    /// Same as `listen_with_client` except that the returned stream opens a new connection
+    /// whenever the current one fails, waiting between attempts according to `policy`.
+    ///
+    /// See the [`reconnect`](reconnect/index.html) module for more information.
+    #[cfg(feature = "runtime")]
+    pub fn listen_reconnecting_with_client<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        policy: ReconnectPolicy,
+    ) -> ReconnectingStream<impl FnMut() -> FutureTwitterStream + 'a, TokioClock>
+    where
+        T: Clone + 'a,
+        Conn: Connect + Clone + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let builder = self.clone();
+        let client = client.clone();
+        ReconnectingStream::new(
+            move || builder.listen_with_client(&client),
+            policy,
+            TokioClock,
+        )
+    }
+
+    /// Same as `listen_with_client` except that the filter of the returned stream can be
+    /// changed while it runs, through `ManagedStream::handle`. The `track`, `follow` and
+    /// `locations` parameters are taken from `params` instead of the builder.
+    ///
+    /// See the [`managed`](managed/index.html) module for more information.
+    pub fn listen_managed_with_client<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        params: FilterParams,
+    ) -> ManagedStream<
+        impl FnMut(&FilterParams) -> MapOk<FutureTwitterStream, fn(TwitterStream) -> Messages> + 'a,
+    >
+    where
+        T: Clone + 'a,
+        Conn: Connect + Clone + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + Payload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let builder = self.clone();
+        let client = client.clone();
+        ManagedStream::new(
+            move |params: &FilterParams| {
+                let mut builder = builder.clone();
+                builder.params(params);
+                builder
+                    .listen_with_client(&client)
+                    .map_ok(TwitterStream::messages as fn(_) -> _)
+            },
+            params,
+        )
+    }
 }
 
-impl<'a, C, A> StreamBuilder<'a, Glyph<C, A>> {
+impl<'a, T> StreamBuilder<'a, T> {
     /// Reset the HTTP request method to be used when connecting
     /// to the server.
     pub fn method(&mut self, method: RequestMethod) -> &mut Self {
@@ -1422,20 +1649,56 @@ impl<'a, C, A> StreamBuilder<'a, Glyph<C, A>> {
         self
     }
 
-    /// Reset the glyph to be used to log into Twitter.
-    pub fn glyph(&mut self, glyph: Glyph<C, A>) -> &mut Self {
+    /// Reset the credentials to be used to log into Twitter, e.g. a `Glyph` or an
+    /// `auth::Bearer`.
+    pub fn glyph(&mut self, glyph: T) -> &mut Self {
         self.glyph = glyph;
         self
     }
 
-    /// Set a timeout for the stream.
+    /// Set the connect, first-byte and inactivity timeouts at once.
+    ///
+    /// Passing `None` disables all of them.
+    #[cfg(feature = "runtime")]
+    pub fn timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.inner.timeouts = Timeouts::new(timeout);
+        self
+    }
+
+    /// Set the time allowed to establish the connection and receive the response headers,
+    /// after which the stream fails with `Error::ConnectTimeout`.
+    ///
+    /// Passing `None` disables the timeout.
+    ///
+    /// Default is 30 seconds.
+    #[cfg(feature = "runtime")]
+    pub fn connect_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.inner.timeouts.connect = timeout.into();
+        self
+    }
+
+    /// Set the time allowed between the response headers and the first data of the stream,
+    /// after which the stream fails with `Error::FirstByteTimeout`.
     ///
     /// Passing `None` disables the timeout.
     ///
     /// Default is 90 seconds.
     #[cfg(feature = "runtime")]
-    pub fn timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
-        self.inner.timeout = timeout.into();
+    pub fn first_byte_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.inner.timeouts.first_byte = timeout.into();
+        self
+    }
+
+    /// Set the time allowed without any data, after which the stream fails with
+    /// `Error::InactivityTimeout`. Twitter sends a keep-alive newline every 30 seconds, so
+    /// this should be a multiple of that.
+    ///
+    /// Passing `None` disables the timeout.
+    ///
+    /// Default is 90 seconds.
+    #[cfg(feature = "runtime")]
+    pub fn inactivity_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.inner.timeouts.inactivity = timeout.into();
         self
     }
 
@@ -1507,6 +1770,15 @@ impl<'a, C, A> StreamBuilder<'a, Glyph<C, A>> {
         self
     }
 
+    /// Set the `track`, `follow` and `locations` parameters from `params`. Empty parameters are
+    /// unset.
+    pub fn params(&mut self, params: &'a FilterParams) -> &mut Self {
+        self.inner.track = Some(params.track()).filter(|t| !t.is_empty());
+        self.inner.follow = Some(params.follow()).filter(|f| !f.is_empty());
+        self.inner.locations = Some(params.locations()).filter(|l| !l.is_empty());
+        self
+    }
+
     /// The `count` parameter.
     /// This parameter requires elevated access to use.
     ///
@@ -1519,23 +1791,40 @@ impl<'a, C, A> StreamBuilder<'a, Glyph<C, A>> {
     }
 }
 
+impl TwitterStream {
+    /// Returns the rate limit headers of the response which opened the stream.
+    pub fn rate_limit(&self) -> &RateLimitStatus {
+        &self.rate_limit
+    }
+
+    /// Turn the stream into one yielding `StreamMessage`s parsed from the JSON strings.
+    pub fn messages(self) -> Messages {
+        Messages::new(self)
+    }
+
+    /// Turn the stream into one yielding Tweets along with the scores `analyzer`
+    /// gives to their text. Messages other than Tweets are skipped.
+    pub fn with_sentiment(self, analyzer: SentimentIntensityAnalyzer<'_>) -> SentimentStream<'_> {
+        SentimentStream::new(self.messages(), analyzer)
+    }
+
+    /// Turn the stream into one yielding Tweets along with the scores the analyzer of their
+    /// language gives to their text, or an `UnsupportedLanguage` error if `languages` has no
+    /// analyzer for it. Messages other than Tweets are skipped.
+    pub fn with_languages(self, languages: Languages<'_>) -> LanguageStream<'_> {
+        LanguageStream::new(self.messages(), languages)
+    }
+}
+
 #[cfg(feature = "tls")]
 impl TwitterStream {
     /// A shorthand for `StreamBuilder::filter().listen()`.
-    pub fn filter<C, A>(glyph: Glyph<C, A>) -> Result<FutureTwitterStream, error::TlsError>
-    where
-        C: Borrow<str>,
-        A: Borrow<str>,
-    {
+    pub fn filter<T: Authenticate>(glyph: T) -> Result<FutureTwitterStream, error::TlsError> {
         StreamBuilder::filter(glyph).listen()
     }
 
     /// A shorthand for `StreamBuilder::sample().listen()`.
-    pub fn sample<C, A>(glyph: Glyph<C, A>) -> Result<FutureTwitterStream, error::TlsError>
-    where
-        C: Borrow<str>,
-        A: Borrow<str>,
-    {
+    pub fn sample<T: Authenticate>(glyph: T) -> Result<FutureTwitterStream, error::TlsError> {
         StreamBuilder::sample(glyph).listen()
     }
 }
@@ -1544,28 +1833,40 @@ impl Future for FutureTwitterStream {
     type Output = Result<TwitterStream, Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(ref mut error) = self.error {
+            let body = ready!(error.body.poll_unpin(cx));
+            let error = Error::from_response(error.status, &error.headers, body);
+            return Poll::Ready(Err(error));
+        }
+
         let res = ready!(self.response.poll_unpin(cx))?;
         let (parts, body) = res.into_parts();
         let Parts {
             status, headers, ..
         } = parts;
 
-        if StatusCode::OK != status {
-            return Poll::Ready(Err(Error::Http(status)));
+        let encoding = Encoding::from_headers(&headers);
+
+        if !status.is_success() {
+            // An error body with an unsupported encoding is read as is, so that the error still
+            // reports the status of the response.
+            let encoding = encoding.unwrap_or(Encoding::Identity);
+            let body = Decoder::new(timeout_to_stream(&self.response, body), encoding);
+            let body = ReadPrefix::new(body, error::MAX_ERROR_BODY);
+            self.error = Some(ErrorResponse {
+                status,
+                headers,
+                body,
+            });
+            return self.poll(cx);
         }
 
-        let body = timeout_to_stream(&self.response, body);
-        let use_brotli = headers
-            .get_all(CONTENT_ENCODING)
-            .iter()
-            .any(|e| e == "Brotli");
-        let inner = if use_brotli {
-            Lines::new(Brotli::Brotli(body))
-        } else {
-            Lines::new(Brotli::identity(body))
-        };
+        let body = Decoder::new(timeout_to_stream(&self.response, body), encoding?);
+        // The headers are informational, so malformed ones do not fail the connection.
+        let rate_limit = RateLimitStatus::from_headers_lossy(&headers).0;
+        let inner = Lines::new(body);
 
-        Poll::Ready(Ok(TwitterStream { inner }))
+        Poll::Ready(Ok(TwitterStream { inner, rate_limit }))
     }
 }
 
@@ -1590,3 +1891,45 @@ fn is_json_whitespace(c: u8) -> bool {
     b" \t\n\r".contains(&c)
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn idioms_check(idioms: &[(&str, f64)], text: &str, sentiments: Vec<f64>) -> Vec<f64> {
+        let idioms: HashMap<&str, f64> = idioms.iter().cloned().collect();
+        let mut analyzer = SentimentIntensityAnalyzer::new();
+        analyzer.idioms(Lexicon::from(&idioms));
+
+        let glyphs: Vec<&str> = text.split(' ').collect();
+        let mut sentiments = sentiments;
+        analyzer.idioms_check(&glyphs, &mut sentiments, None);
+        sentiments
+    }
+
+    #[test]
+    fn idioms() {
+        let text = "I feel under the weather";
+        let mut analyzer = SentimentIntensityAnalyzer::new();
+        let with_idioms = analyzer.polarity_scores(text).compound;
+        analyzer.idioms(Lexicon::new());
+        assert!(with_idioms < 0.0);
+        assert!(with_idioms < analyzer.polarity_scores(text).compound);
+
+        let idioms = [("kiss of", 1.0), ("kiss of death", -1.5), ("of death", 2.0)];
+        assert_eq!(
+            idioms_check(&idioms, "a Kiss of death", vec![0.0, 1.2, 0.0, -2.9]),
+            [0.0, 0.0, 0.0, -1.5]
+        );
+        assert_eq!(
+            idioms_check(&idioms, "a kiss of life", vec![0.0, 1.2, 0.0, 1.6]),
+            [0.0, 0.0, 1.0, 1.6]
+        );
+
+        let idioms = [("under the weather", -2.0)];
+        assert_eq!(
+            idioms_check(&idioms, "not under the weather", vec![0.0; 4]),
+            [0.0, 0.0, 0.0, -2.0 * NEGATION_SCALAR]
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! Filtered streams whose filter can be changed while they run.
+//!
+//! Changing the `track`, `follow` or `locations` parameters of a stream requires a new
+//! connection. `ManagedStream` takes care of it: when new `FilterParams` are set through a
+//! `FilterHandle`, it opens a connection with them while still reading from the current one, and
+//! switches over once the first message arrives on the new connection. Tweets delivered by both
+//! connections around the switch are only yielded once.
+//!
+//! Keep in mind that Twitter limits the number of concurrent connections and the rate at which
+//! a client may connect, so the filter should not be changed too often.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::filters::FilterParams;
+//! use khipu::{Glyph, StreamBuilder, StreamMessage};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let glyph: Glyph = unimplemented!();
+//! let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new().unwrap());
+//! let mut params = FilterParams::new();
+//! params.track_phrase("rustlang").unwrap();
+//!
+//! let mut stream = StreamBuilder::filter(glyph).listen_managed_with_client(&client, params);
+//! let handle = stream.handle();
+//!
+//! while let Some(msg) = stream.try_next().await.unwrap() {
+//!     if let StreamMessage::Tweet(tweet) = msg {
+//!         if tweet.text.contains("async") {
+//!             let mut params = FilterParams::new();
+//!             params.track_phrase("rustlang").unwrap();
+//!             params.track_phrase("async await").unwrap();
+//!             handle.set(params);
+//!         }
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+use futures_util::{ready, FutureExt, StreamExt};
+
+use crate::error::Error;
+use crate::filters::FilterParams;
+use crate::message::StreamMessage;
+
+/// The default number of recent Tweet IDs remembered to detect duplicates.
+const DEDUP_WINDOW: usize = 10_000;
+
+/// Opens connections for a `ManagedStream`.
+///
+/// This is implemented for any `FnMut(&FilterParams) -> impl Future<Output = Result<S, Error>>`.
+pub trait Connector {
+    /// The future resolving to a connection.
+    type Future: Future<Output = Result<Self::Stream, Error>>;
+    /// The connection.
+    type Stream;
+
+    /// Starts opening a new connection filtered by `params`.
+    fn connect(&mut self, params: &FilterParams) -> Self::Future;
+}
+
+/// A stream returned by `StreamBuilder::listen_managed_with_client`.
+///
+/// It yields the messages of the current connection. An error opening a connection with new
+/// parameters is yielded as well, after which the stream carries on with the current
+/// connection. The stream ends when the current connection ends and no new connection is
+/// being opened.
+pub struct ManagedStream<K: Connector> {
+    connector: K,
+    params: FilterParams,
+    current: State<K::Future, K::Stream>,
+    next: Option<Next<K::Future, K::Stream>>,
+    shared: Arc<Mutex<Shared>>,
+    seen: Seen,
+}
+
+/// A handle to change the filter of a `ManagedStream`.
+///
+/// Clones of a `FilterHandle` control the same stream.
+#[derive(Clone, Debug)]
+pub struct FilterHandle {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    params: Option<FilterParams>,
+    waker: Option<Waker>,
+}
+
+enum State<F, S> {
+    Connecting(F),
+    Connected(S),
+    Done,
+}
+
+/// A connection with new parameters, which replaces the current one once it delivers a message.
+struct Next<F, S> {
+    params: FilterParams,
+    state: State<F, S>,
+}
+
+/// The IDs of the most recent Tweets, oldest first.
+struct Seen {
+    ids: HashSet<u64>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl<F, Fut, S> Connector for F
+where
+    F: FnMut(&FilterParams) -> Fut,
+    Fut: Future<Output = Result<S, Error>>,
+{
+    type Future = Fut;
+    type Stream = S;
+
+    fn connect(&mut self, params: &FilterParams) -> Fut {
+        self(params)
+    }
+}
+
+impl<K: Connector> ManagedStream<K> {
+    /// Creates a stream opening its connections with `connector`, starting with `params`.
+    pub fn new(mut connector: K, params: FilterParams) -> Self {
+        let current = State::Connecting(connector.connect(&params));
+        ManagedStream {
+            connector,
+            params,
+            current,
+            next: None,
+            shared: Default::default(),
+            seen: Seen::new(DEDUP_WINDOW),
+        }
+    }
+
+    /// Returns a handle to change the filter of the stream.
+    pub fn handle(&self) -> FilterHandle {
+        FilterHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Returns the parameters of the current connection.
+    pub fn params(&self) -> &FilterParams {
+        &self.params
+    }
+
+    /// Returns whether a connection with new parameters is being opened.
+    pub fn is_switching(&self) -> bool {
+        self.next.is_some()
+    }
+
+    /// Set the number of recent Tweet IDs remembered to detect duplicates, 10,000 by default.
+    /// Setting it to zero disables de-duplication.
+    pub fn dedup_window(&mut self, capacity: usize) -> &mut Self {
+        self.seen.resize(capacity);
+        self
+    }
+
+    fn update(&mut self, params: FilterParams) {
+        let connection = State::Connecting(self.connector.connect(&params));
+        if let State::Connected(_) = self.current {
+            // Any connection still being opened for older parameters is abandoned.
+            self.next = Some(Next {
+                params,
+                state: connection,
+            });
+        } else {
+            // Nothing is being received, so there is nothing to lose by replacing it at once.
+            self.params = params;
+            self.current = connection;
+            self.next = None;
+        }
+    }
+}
+
+impl<K> ManagedStream<K>
+where
+    K: Connector,
+    K::Future: Unpin,
+    K::Stream: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+{
+    /// Polls the connection with new parameters, switching over to it on its first message.
+    fn poll_next_connection(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Option<Result<StreamMessage, Error>> {
+        loop {
+            let state = &mut self.next.as_mut()?.state;
+            match *state {
+                State::Connecting(ref mut future) => match future.poll_unpin(cx) {
+                    Poll::Ready(Ok(stream)) => *state = State::Connected(stream),
+                    Poll::Ready(Err(e)) => {
+                        self.next = None;
+                        return Some(Err(e));
+                    }
+                    Poll::Pending => return None,
+                },
+                State::Connected(ref mut stream) => match stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(msg))) => {
+                        let next = self.next.take().unwrap();
+                        self.params = next.params;
+                        self.current = next.state;
+                        return if self.seen.insert(&msg) {
+                            Some(Ok(msg))
+                        } else {
+                            None
+                        };
+                    }
+                    Poll::Ready(Some(Err(e))) => {
+                        self.next = None;
+                        return Some(Err(e));
+                    }
+                    Poll::Ready(None) => {
+                        // Closed before delivering anything. Keep the current connection.
+                        self.next = None;
+                        return None;
+                    }
+                    Poll::Pending => return None,
+                },
+                State::Done => {
+                    self.next = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<K> Stream for ManagedStream<K>
+where
+    K: Connector + Unpin,
+    K::Future: Unpin,
+    K::Stream: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+{
+    type Item = Result<StreamMessage, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        let update = {
+            let mut shared = this.shared.lock().unwrap();
+            shared.waker = Some(cx.waker().clone());
+            shared.params.take()
+        };
+        if let Some(params) = update {
+            this.update(params);
+        }
+
+        if let Some(item) = this.poll_next_connection(cx) {
+            return Poll::Ready(Some(item));
+        }
+
+        loop {
+            match this.current {
+                State::Connecting(ref mut future) => match ready!(future.poll_unpin(cx)) {
+                    Ok(stream) => this.current = State::Connected(stream),
+                    Err(e) => {
+                        this.current = State::Done;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                },
+                State::Connected(ref mut stream) => match ready!(stream.poll_next_unpin(cx)) {
+                    Some(Ok(msg)) => {
+                        if this.seen.insert(&msg) {
+                            return Poll::Ready(Some(Ok(msg)));
+                        }
+                    }
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                    None => this.current = State::Done,
+                },
+                State::Done if this.next.is_some() => return Poll::Pending,
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl FilterHandle {
+    /// Switch the stream over to `params`. A change which is still pending is abandoned in favor
+    /// of this one.
+    pub fn set(&self, params: FilterParams) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.params = Some(params);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Seen {
+    fn new(capacity: usize) -> Self {
+        Seen {
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.order.len() > capacity {
+            self.forget_oldest();
+        }
+    }
+
+    /// Records the ID of a Tweet, returning `false` if it was seen recently.
+    /// Messages other than Tweets are always new.
+    fn insert(&mut self, msg: &StreamMessage) -> bool {
+        let id = match *msg {
+            StreamMessage::Tweet(ref tweet) => tweet.id,
+            StreamMessage::Payload(ref payload) => match payload.data.id.parse() {
+                Ok(id) => id,
+                Err(_) => return true,
+            },
+            _ => return true,
+        };
+
+        if self.capacity == 0 {
+            return true;
+        }
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            self.forget_oldest();
+        }
+        true
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some(id) = self.order.pop_front() {
+            self.ids.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use futures_executor::block_on_stream;
+    use futures_util::future;
+    use futures_util::stream;
+
+    use crate::message::tweet;
+
+    type Connection = stream::Iter<std::vec::IntoIter<Result<StreamMessage, Error>>>;
+    type Attempt = Result<Vec<Result<StreamMessage, Error>>, Error>;
+
+    fn message(id: u64) -> Result<StreamMessage, Error> {
+        Ok(StreamMessage::Tweet(tweet(id, "")))
+    }
+
+    fn ids(messages: Vec<Result<StreamMessage, Error>>) -> Vec<u64> {
+        messages
+            .into_iter()
+            .map(|msg| match msg {
+                Ok(StreamMessage::Tweet(tweet)) => tweet.id,
+                msg => panic!("expected a Tweet, got {:?}", msg),
+            })
+            .collect()
+    }
+
+    /// Returns a connector serving the given messages for each `track` parameter, once.
+    fn connector(
+        attempts: Vec<(&'static str, Attempt)>,
+    ) -> impl FnMut(&FilterParams) -> future::Ready<Result<Connection, Error>> {
+        let mut attempts: HashMap<_, _> = attempts.into_iter().collect();
+        move |params: &FilterParams| {
+            let attempt = attempts.remove(params.track()).unwrap();
+            future::ready(attempt.map(stream::iter))
+        }
+    }
+
+    fn track(phrase: &str) -> FilterParams {
+        let mut params = FilterParams::new();
+        params.track_phrase(phrase).unwrap();
+        params
+    }
+
+    #[test]
+    fn switch() {
+        let connector = connector(vec![
+            ("a", Ok(vec![message(1), message(2), message(3)])),
+            ("b", Ok(vec![message(2), message(3), message(4)])),
+        ]);
+        let stream = ManagedStream::new(connector, track("a"));
+        let handle = stream.handle();
+        let mut stream = block_on_stream(stream);
+
+        assert_eq!(ids(stream.by_ref().take(2).collect()), [1, 2]);
+        handle.set(track("b"));
+        // Tweet 2 arrives on both connections.
+        assert_eq!(ids(stream.by_ref().collect()), [3, 4]);
+    }
+
+    #[test]
+    fn failed_switch() {
+        let connector = connector(vec![
+            ("a", Ok(vec![message(1), message(2)])),
+            ("b", Err(Error::custom("refused"))),
+        ]);
+        let stream = ManagedStream::new(connector, track("a"));
+        let handle = stream.handle();
+        let mut stream = block_on_stream(stream);
+
+        assert_eq!(ids(stream.by_ref().take(1).collect()), [1]);
+        handle.set(track("b"));
+        match stream.next() {
+            Some(Err(Error::Custom(_))) => (),
+            r => panic!("expected an error, got {:?}", r),
+        }
+        assert_eq!(ids(stream.collect()), [2]);
+    }
+
+    #[test]
+    fn dedup_window() {
+        let mut seen = Seen::new(2);
+        assert!(seen.insert(&message(1).unwrap()));
+        assert!(seen.insert(&message(2).unwrap()));
+        assert!(!seen.insert(&message(1).unwrap()));
+        assert!(seen.insert(&message(3).unwrap()));
+        // 1 was forgotten when 3 was inserted.
+        assert!(seen.insert(&message(1).unwrap()));
+
+        seen.resize(0);
+        assert!(seen.insert(&message(1).unwrap()));
+        assert!(seen.insert(&message(1).unwrap()));
+    }
+}
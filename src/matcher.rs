@@ -0,0 +1,315 @@
+//! Client-side matching of Tweets to the `track` phrases which selected them.
+//!
+//! The Streaming API does not tell which of the tracked phrases a Tweet matched. `TrackMatcher`
+//! finds out by applying the [`track` semantics][1] of the API again:
+//!
+//! * the terms of a phrase are separated by spaces, and a phrase matches if all of its terms
+//!   appear in the Tweet, in any order,
+//! * matching is case-insensitive,
+//! * punctuation around a word of the Tweet is ignored, so `twitter` matches `twitter.`,
+//!   `#twitter` and `@twitter`, but punctuation in a term is not, so `#twitter` only matches
+//!   `#twitter`,
+//! * terms are looked up in the text, the expanded and display URLs, the hashtags and the
+//!   mentioned screen names of the Tweet, and of the Tweets it retweets or quotes.
+//!
+//! URLs written in the text are located with [`url_entities`](../fn.url_entities.html) and matched like
+//! expanded URLs, i.e. a term matches a URL if it appears in it between non-alphanumeric
+//! characters, as `twitter` does in `https://twitter.com/rustlang`.
+//!
+//! [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/basic-stream-parameters#track
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::filters::FilterParams;
+//! use khipu::matcher::TrackMatcher;
+//! use khipu::{Glyph, StreamBuilder};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let glyph: Glyph = unimplemented!();
+//! let mut params = FilterParams::new();
+//! params.track_phrase("rustlang").unwrap();
+//! params.track_phrase("async await").unwrap();
+//!
+//! let matcher = TrackMatcher::from_params(&params);
+//! let stream = StreamBuilder::filter(glyph)
+//!     .params(&params)
+//!     .listen()
+//!     .unwrap()
+//!     .await
+//!     .unwrap();
+//! let mut tweets = stream.messages().match_track(matcher);
+//!
+//! while let Some(matched) = tweets.try_next().await.unwrap() {
+//!     println!("{:?}: {}", matched.phrases, matched.tweet.text);
+//! }
+//! # }
+//! ```
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::error::Error;
+use crate::filters::FilterParams;
+use crate::message::{Messages, StreamMessage, Tweet};
+use crate::url_entities;
+
+/// Finds the tracked phrases matched by a Tweet.
+#[derive(Clone, Debug, Default)]
+pub struct TrackMatcher {
+    phrases: Vec<Phrase>,
+}
+
+#[derive(Clone, Debug)]
+struct Phrase {
+    phrase: String,
+    terms: Vec<String>,
+}
+
+/// A Tweet along with the tracked phrases it matched, yielded by `MatchStream`.
+#[derive(Clone, Debug)]
+pub struct Matched {
+    /// The Tweet.
+    pub tweet: Tweet,
+    /// The phrases matched by the Tweet, in the order they were given to the `TrackMatcher`.
+    /// This is empty for Tweets delivered because of the `follow` or `locations` parameters.
+    pub phrases: Vec<String>,
+}
+
+/// A stream adapter returned by `Messages::match_track`.
+/// It yields Tweets along with the tracked phrases they matched, skipping other messages.
+pub struct MatchStream {
+    inner: Messages,
+    matcher: TrackMatcher,
+}
+
+/// The lowercase words and URLs of a Tweet.
+#[derive(Default)]
+struct Haystack {
+    words: Vec<String>,
+    urls: Vec<String>,
+}
+
+impl TrackMatcher {
+    /// Creates a matcher for the given phrases. Blank phrases are ignored.
+    pub fn new<I>(phrases: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let phrases = phrases
+            .into_iter()
+            .filter_map(|phrase| {
+                let phrase = phrase.as_ref().trim();
+                let terms: Vec<_> = phrase.split_whitespace().map(str::to_lowercase).collect();
+                if terms.is_empty() {
+                    None
+                } else {
+                    Some(Phrase {
+                        phrase: phrase.to_owned(),
+                        terms,
+                    })
+                }
+            })
+            .collect();
+        TrackMatcher { phrases }
+    }
+
+    /// Creates a matcher for the tracked phrases of `params`.
+    pub fn from_params(params: &FilterParams) -> Self {
+        TrackMatcher::new(params.track_phrases())
+    }
+
+    /// Returns the phrases matched by `tweet`, in the order they were given.
+    pub fn matches(&self, tweet: &Tweet) -> Vec<&str> {
+        let mut haystack = Haystack::default();
+        haystack.add_tweet(tweet);
+        if let Some(ref retweeted) = tweet.retweeted_status {
+            haystack.add_tweet(retweeted);
+        }
+        if let Some(ref quoted) = tweet.quoted_status {
+            haystack.add_tweet(quoted);
+        }
+        self.find(&haystack)
+    }
+
+    /// Returns the phrases matched by a text, in the order they were given.
+    pub fn matches_text(&self, text: &str) -> Vec<&str> {
+        let mut haystack = Haystack::default();
+        haystack.add_text(text);
+        self.find(&haystack)
+    }
+
+    /// Pairs `tweet` with the phrases it matched.
+    pub fn annotate(&self, tweet: Tweet) -> Matched {
+        let phrases = self.matches(&tweet).into_iter().map(String::from).collect();
+        Matched { tweet, phrases }
+    }
+
+    fn find(&self, haystack: &Haystack) -> Vec<&str> {
+        self.phrases
+            .iter()
+            .filter(|p| p.terms.iter().all(|term| haystack.contains(term)))
+            .map(|p| &*p.phrase)
+            .collect()
+    }
+}
+
+impl MatchStream {
+    /// Returns the matcher used to annotate the Tweets.
+    pub fn matcher(&self) -> &TrackMatcher {
+        &self.matcher
+    }
+}
+
+impl Stream for MatchStream {
+    type Item = Result<Matched, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let StreamMessage::Tweet(tweet) = ready_some!(self.inner.poll_next_unpin(cx))? {
+                return Poll::Ready(Some(Ok(self.matcher.annotate(tweet))));
+            }
+        }
+    }
+}
+
+impl Messages {
+    /// Turn the stream into one yielding Tweets along with the tracked phrases they matched.
+    pub fn match_track(self, matcher: TrackMatcher) -> MatchStream {
+        MatchStream {
+            inner: self,
+            matcher,
+        }
+    }
+}
+
+impl Haystack {
+    fn add_tweet(&mut self, tweet: &Tweet) {
+        self.add_text(tweet.full_text());
+
+        let entities = tweet.full_entities();
+        for url in &entities.urls {
+            self.urls
+                .extend(url.expanded_url.iter().map(|u| u.to_lowercase()));
+            self.urls
+                .extend(url.display_url.iter().map(|u| u.to_lowercase()));
+        }
+        for hashtag in &entities.hashtags {
+            self.words.push(format!("#{}", hashtag.text.to_lowercase()));
+        }
+        for mention in &entities.user_mentions {
+            self.words
+                .push(format!("@{}", mention.screen_name.to_lowercase()));
+        }
+    }
+
+    fn add_text(&mut self, text: &str) {
+        let mut last_pos = 0;
+        for url in url_entities(text) {
+            let (start, end) = url.range;
+            self.add_words(&text[last_pos..start]);
+            self.urls.push(text[start..end].to_lowercase());
+            last_pos = end;
+        }
+        self.add_words(&text[last_pos..]);
+    }
+
+    fn add_words(&mut self, text: &str) {
+        self.words
+            .extend(text.split_whitespace().map(str::to_lowercase));
+    }
+
+    /// Returns whether the lowercase `term` matches a word or a URL.
+    fn contains(&self, term: &str) -> bool {
+        self.words.iter().any(|word| word_matches(word, term))
+            || self.urls.iter().any(|url| url_matches(url, term))
+    }
+}
+
+/// Punctuation around `word` is ignored, but punctuation in `term` must be present in `word`.
+fn word_matches(word: &str, term: &str) -> bool {
+    word == term || (trim_punctuation(word) == trim_punctuation(term) && word.contains(term))
+}
+
+/// `term` must appear in `url` between non-alphanumeric characters.
+fn url_matches(url: &str, term: &str) -> bool {
+    url.match_indices(term).any(|(start, _)| {
+        let end = start + term.len();
+        let before = url[..start].chars().next_back();
+        let after = url[end..].chars().next();
+        before.filter(|c| c.is_alphanumeric()).is_none()
+            && after.filter(|c| c.is_alphanumeric()).is_none()
+    })
+}
+
+fn trim_punctuation(s: &str) -> &str {
+    s.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::message::tweet;
+
+    #[test]
+    fn terms() {
+        let matcher = TrackMatcher::new(&["Twitter"]);
+        for text in &[
+            "TWITTER",
+            "twitter",
+            "\u{201c}Twitter\u{201d}",
+            "twitter.",
+            "#twitter",
+            "@twitter",
+            "http://twitter.com",
+        ] {
+            assert_eq!(matcher.matches_text(text), ["Twitter"], "{}", text);
+        }
+        for text in &["TwitterTracker", "#newtwitter", "Twitter's"] {
+            assert!(matcher.matches_text(text).is_empty(), "{}", text);
+        }
+
+        let matcher = TrackMatcher::new(&["#twitter", "hello."]);
+        assert_eq!(matcher.matches_text("#Twitter!"), ["#twitter"]);
+        assert!(matcher.matches_text("@twitter twitter hello").is_empty());
+        assert_eq!(matcher.matches_text("hello."), ["hello."]);
+    }
+
+    #[test]
+    fn phrases() {
+        let matcher = TrackMatcher::new(&["twitter api", "twitter streaming", "rust"]);
+        assert_eq!(
+            matcher.matches_text("The Twitter API is awesome"),
+            ["twitter api"]
+        );
+        assert_eq!(
+            matcher.matches_text("Twitter has a streaming API"),
+            ["twitter api", "twitter streaming"]
+        );
+        assert!(matcher.matches_text("I'm new to Twitter").is_empty());
+    }
+
+    #[test]
+    fn entities() {
+        let matcher = TrackMatcher::new(&["rustlang", "example", "ferris"]);
+        let mut tweet = tweet(1, "Read this https://t.co/abc #RustLang");
+        tweet.entities = serde_json::from_str(
+            r#"{"hashtags":[{"text":"RustLang","indices":[27,36]}],
+            "urls":[{"url":"https://t.co/abc","expanded_url":"https://example.com/post",
+            "display_url":"example.com/post","indices":[10,26]}],
+            "user_mentions":[{"id":1,"screen_name":"Ferris","name":"Ferris","indices":[0,0]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(matcher.matches(&tweet), ["rustlang", "example", "ferris"]);
+
+        let matched = matcher.annotate(tweet);
+        assert_eq!(matched.phrases, ["rustlang", "example", "ferris"]);
+    }
+}
@@ -0,0 +1,482 @@
+//! Typed messages delivered by the Streaming API.
+//!
+//! Besides Tweets, a stream carries a handful of control messages: deletion and location
+//! scrubbing notices, rate limit notices, stall warnings, disconnect messages and withheld
+//! content notices. `StreamMessage` tells them apart and deserializes each into its own type.
+//! Message types unknown to this crate are handed back untouched as `StreamMessage::Unknown`.
+//! So are messages of a known type which fail to deserialize, e.g. because Twitter changed their
+//! format, so that a single odd message does not end a stream consumed with `try_next`.
+//!
+//! Messages of the v2 filtered stream (see the `v2` module) are recognized by their `data` field
+//! and returned as `StreamMessage::Payload`.
+//!
+//! See the [Twitter Developer Documentation][1] for the full list of message types.
+//!
+//! [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/streaming-message-types
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::TwitterStream;
+
+/// A message from the Streaming API.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum StreamMessage {
+    /// A Tweet, Retweet or Quote Tweet.
+    Tweet(Tweet),
+    /// A notice that a Tweet was deleted.
+    Delete(Delete),
+    /// A notice that location information should be stripped from a range of Tweets.
+    ScrubGeo(ScrubGeo),
+    /// A notice that the filter matched more Tweets than the rate limit allows to be delivered.
+    Limit(Limit),
+    /// A warning that the client is falling behind and may be disconnected.
+    StallWarning(StallWarning),
+    /// A notice that the server is about to close the connection.
+    Disconnect(Disconnect),
+    /// A notice that a Tweet was withheld in some countries.
+    StatusWithheld(StatusWithheld),
+    /// A notice that a user was withheld in some countries.
+    UserWithheld(UserWithheld),
+    /// A Tweet delivered by the v2 filtered stream, along with the objects it references and
+    /// the rules it matched.
+    Payload(crate::v2::Payload),
+    /// A message of a type this crate does not know about, or of a known type but which could
+    /// not be deserialized. The enclosed value is the raw JSON line as received from the API.
+    Unknown(string::String<Bytes>),
+}
+
+/// A Tweet delivered by the Streaming API.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Tweet {
+    /// The UTC time at which the Tweet was created.
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub created_at: DateTime<Utc>,
+    /// The numeric ID of the Tweet.
+    pub id: u64,
+    /// The text of the Tweet. This is truncated for Tweets longer than 140 characters, in which
+    /// case the full text is in `extended_tweet`.
+    pub text: String,
+    /// Whether `text` was truncated.
+    #[serde(default)]
+    pub truncated: bool,
+    /// The ID of the Tweet this one replies to, if any.
+    pub in_reply_to_status_id: Option<u64>,
+    /// The ID of the user this Tweet replies to, if any.
+    pub in_reply_to_user_id: Option<u64>,
+    /// The screen name of the user this Tweet replies to, if any.
+    pub in_reply_to_screen_name: Option<String>,
+    /// The author of the Tweet.
+    pub user: User,
+    /// The full text and entities of a Tweet longer than 140 characters.
+    pub extended_tweet: Option<ExtendedTweet>,
+    /// The Tweet quoted by this one, if any.
+    pub quoted_status: Option<Box<Tweet>>,
+    /// The original Tweet, if this one is a Retweet.
+    pub retweeted_status: Option<Box<Tweet>>,
+    /// Entities parsed from `text` by Twitter.
+    #[serde(default)]
+    pub entities: TweetEntities,
+    /// The BCP 47 language identifier of the Tweet, as detected by Twitter.
+    pub lang: Option<String>,
+}
+
+impl Tweet {
+    /// Returns the untruncated text of the Tweet.
+    pub fn full_text(&self) -> &str {
+        match self.extended_tweet {
+            Some(ref ext) => &ext.full_text,
+            None => &self.text,
+        }
+    }
+
+    /// Returns the entities matching `full_text`.
+    pub fn full_entities(&self) -> &TweetEntities {
+        match self.extended_tweet {
+            Some(ref ext) => &ext.entities,
+            None => &self.entities,
+        }
+    }
+}
+
+/// The full text and entities of a Tweet longer than 140 characters.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExtendedTweet {
+    /// The untruncated text of the Tweet.
+    pub full_text: String,
+    /// Entities parsed from `full_text` by Twitter.
+    #[serde(default)]
+    pub entities: TweetEntities,
+}
+
+/// The author of a Tweet.
+#[derive(Clone, Debug, Deserialize)]
+pub struct User {
+    /// The numeric ID of the user.
+    pub id: u64,
+    /// The display name of the user.
+    pub name: String,
+    /// The handle of the user, without the leading `@`.
+    pub screen_name: String,
+    /// The user-defined location of the account, if any.
+    pub location: Option<String>,
+    /// The number of followers of the account.
+    #[serde(default)]
+    pub followers_count: u64,
+    /// Whether the account is verified.
+    #[serde(default)]
+    pub verified: bool,
+}
+
+/// Entities parsed from the text of a Tweet by Twitter.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TweetEntities {
+    /// Hashtags in the Tweet.
+    #[serde(default)]
+    pub hashtags: Vec<HashtagEntity>,
+    /// Financial symbols ("cashtags") in the Tweet.
+    #[serde(default)]
+    pub symbols: Vec<HashtagEntity>,
+    /// URLs in the Tweet.
+    #[serde(default)]
+    pub urls: Vec<UrlEntity>,
+    /// User mentions in the Tweet.
+    #[serde(default)]
+    pub user_mentions: Vec<MentionEntity>,
+}
+
+/// A hashtag or financial symbol in a Tweet.
+#[derive(Clone, Debug, Deserialize)]
+pub struct HashtagEntity {
+    /// The text of the hashtag, without the leading `#` or `$`.
+    pub text: String,
+    /// The character offsets of the entity in the Tweet text, including the leading `#` or `$`.
+    pub indices: (usize, usize),
+}
+
+/// A URL in a Tweet.
+#[derive(Clone, Debug, Deserialize)]
+pub struct UrlEntity {
+    /// The t.co URL as it appears in the Tweet text.
+    pub url: String,
+    /// The URL the t.co link points to.
+    pub expanded_url: Option<String>,
+    /// The shortened URL to display to users.
+    pub display_url: Option<String>,
+    /// The character offsets of the URL in the Tweet text.
+    pub indices: (usize, usize),
+}
+
+/// A user mention in a Tweet.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MentionEntity {
+    /// The numeric ID of the mentioned user.
+    pub id: u64,
+    /// The handle of the mentioned user, without the leading `@`.
+    pub screen_name: String,
+    /// The display name of the mentioned user.
+    pub name: String,
+    /// The character offsets of the mention in the Tweet text, including the leading `@`.
+    pub indices: (usize, usize),
+}
+
+/// A notice that a Tweet was deleted. Clients should remove the Tweet from any storage.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Delete {
+    /// The numeric ID of the deleted Tweet.
+    pub id: u64,
+    /// The numeric ID of the author of the deleted Tweet.
+    pub user_id: u64,
+}
+
+/// A notice that location information should be stripped from all Tweets of a user up to and
+/// including a given Tweet.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScrubGeo {
+    /// The numeric ID of the user.
+    pub user_id: u64,
+    /// The numeric ID of the last Tweet to scrub.
+    pub up_to_status_id: u64,
+}
+
+/// A notice that the filter matched more Tweets than could be delivered.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Limit {
+    /// The total number of undelivered Tweets since the connection was opened.
+    pub track: u64,
+}
+
+/// A warning that the client is not reading the stream fast enough.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StallWarning {
+    /// A code identifying the warning, e.g. `FALLING_BEHIND`.
+    pub code: String,
+    /// A human-readable description of the warning.
+    pub message: String,
+    /// How full the server-side queue for the connection is, in percent.
+    pub percent_full: u8,
+}
+
+/// A notice that the server is about to close the connection.
+///
+/// See the [Twitter Developer Documentation][1] for the meaning of each `code`.
+///
+/// [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/streaming-message-types
+#[derive(Clone, Debug, Deserialize)]
+pub struct Disconnect {
+    /// A code identifying the reason of the disconnection.
+    pub code: u16,
+    /// The name of the stream being disconnected.
+    pub stream_name: Option<String>,
+    /// A human-readable description of the reason.
+    pub reason: String,
+}
+
+/// A notice that a Tweet was withheld in some countries.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StatusWithheld {
+    /// The numeric ID of the withheld Tweet.
+    pub id: u64,
+    /// The numeric ID of the author of the withheld Tweet.
+    pub user_id: u64,
+    /// The two-letter country codes of the countries where the Tweet is withheld.
+    pub withheld_in_countries: Vec<String>,
+}
+
+/// A notice that a user was withheld in some countries.
+#[derive(Clone, Debug, Deserialize)]
+pub struct UserWithheld {
+    /// The numeric ID of the withheld user.
+    pub id: u64,
+    /// The two-letter country codes of the countries where the user is withheld.
+    pub withheld_in_countries: Vec<String>,
+}
+
+/// A stream adapter returned by `TwitterStream::messages`.
+/// It yields `StreamMessage`s parsed from the JSON strings returned from the API.
+pub struct Messages {
+    inner: TwitterStream,
+}
+
+#[derive(Deserialize)]
+struct DeleteEnvelope {
+    status: Delete,
+}
+
+const TWITTER_DATETIME_FORMAT: &str = "%a %b %d %H:%M:%S %z %Y";
+
+impl StreamMessage {
+    /// Parses a JSON line from the Streaming API.
+    ///
+    /// Lines which are valid JSON objects but not a known message type, or not a valid message
+    /// of their type, are returned as `StreamMessage::Unknown`. Only lines which are not JSON
+    /// objects are reported as `Error::DeserializeError`.
+    pub fn from_line(line: string::String<Bytes>) -> Result<Self, Error> {
+        let map: Map<String, Value> = serde_json::from_str(&line)?;
+
+        match StreamMessage::from_map(map) {
+            Ok(Some(msg)) => Ok(msg),
+            Ok(None) | Err(_) => Ok(StreamMessage::Unknown(line)),
+        }
+    }
+
+    fn from_map(mut map: Map<String, Value>) -> serde_json::Result<Option<Self>> {
+        macro_rules! take {
+            ($key:expr) => {
+                serde_json::from_value(map.remove($key).unwrap())?
+            };
+        }
+
+        let msg = if map.contains_key("delete") {
+            let DeleteEnvelope { status } = take!("delete");
+            StreamMessage::Delete(status)
+        } else if map.contains_key("scrub_geo") {
+            StreamMessage::ScrubGeo(take!("scrub_geo"))
+        } else if map.contains_key("limit") {
+            StreamMessage::Limit(take!("limit"))
+        } else if map.contains_key("warning") {
+            StreamMessage::StallWarning(take!("warning"))
+        } else if map.contains_key("disconnect") {
+            StreamMessage::Disconnect(take!("disconnect"))
+        } else if map.contains_key("status_withheld") {
+            StreamMessage::StatusWithheld(take!("status_withheld"))
+        } else if map.contains_key("user_withheld") {
+            StreamMessage::UserWithheld(take!("user_withheld"))
+        } else if map.contains_key("data") {
+            StreamMessage::Payload(serde_json::from_value(Value::Object(map))?)
+        } else if map.contains_key("id") && map.contains_key("text") {
+            StreamMessage::Tweet(serde_json::from_value(Value::Object(map))?)
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(msg))
+    }
+}
+
+impl Messages {
+    pub(crate) fn new(inner: TwitterStream) -> Self {
+        Messages { inner }
+    }
+
+    /// Returns the underlying stream of JSON strings.
+    pub fn into_inner(self) -> TwitterStream {
+        self.inner
+    }
+}
+
+impl Stream for Messages {
+    type Item = Result<StreamMessage, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let line = ready_some!(self.inner.poll_next_unpin(cx))?;
+        Poll::Ready(Some(StreamMessage::from_line(line)))
+    }
+}
+
+fn deserialize_datetime<'de, D>(d: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(d)?;
+    DateTime::parse_from_str(&s, TWITTER_DATETIME_FORMAT)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(de::Error::custom)
+}
+
+/// Returns a Tweet with the given ID and text, posted by @TwitterAPI.
+#[cfg(test)]
+pub(crate) fn tweet(id: u64, text: &str) -> Tweet {
+    serde_json::from_value(serde_json::json!({
+        "created_at": "Wed Oct 10 20:19:24 +0000 2018",
+        "id": id,
+        "text": text,
+        "user": {"id": 6253282, "name": "Twitter API", "screen_name": "TwitterAPI"},
+    }))
+    .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use string::TryFrom;
+
+    fn parse(json: &'static str) -> StreamMessage {
+        let line = string::String::try_from(Bytes::from_static(json.as_bytes())).unwrap();
+        StreamMessage::from_line(line).unwrap()
+    }
+
+    #[test]
+    fn tweet() {
+        let msg = parse(
+            r#"{"created_at":"Wed Oct 10 20:19:24 +0000 2018","id":1050118621198921728,
+            "text":"To make room for more expression, we will now count all emojis as equal",
+            "truncated":true,"user":{"id":6253282,"name":"Twitter API","screen_name":"TwitterAPI"},
+            "extended_tweet":{"full_text":"To make room for more expression, we will now count all emojis as equal—including those with gender and skin tone modifiers 👍🏻",
+            "entities":{"hashtags":[],"urls":[],"user_mentions":[],"symbols":[]}},
+            "entities":{"hashtags":[{"text":"emoji","indices":[0,6]}]},"lang":"en"}"#,
+        );
+
+        match msg {
+            StreamMessage::Tweet(tweet) => {
+                assert_eq!(tweet.id, 1050118621198921728);
+                assert_eq!(tweet.user.screen_name, "TwitterAPI");
+                assert_eq!(tweet.created_at.timestamp(), 1539202764);
+                assert!(tweet.full_text().ends_with("\u{1f44d}\u{1f3fb}"));
+                assert_eq!(tweet.entities.hashtags[0].text, "emoji");
+                assert!(tweet.full_entities().hashtags.is_empty());
+                assert_eq!(tweet.lang.as_ref().map(String::as_str), Some("en"));
+            }
+            msg => panic!("expected a Tweet, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn control_messages() {
+        match parse(r#"{"delete":{"status":{"id":1234,"id_str":"1234","user_id":3,"user_id_str":"3"}}}"#) {
+            StreamMessage::Delete(d) => assert_eq!((d.id, d.user_id), (1234, 3)),
+            msg => panic!("expected Delete, got {:?}", msg),
+        }
+        match parse(r#"{"limit":{"track":1234,"timestamp_ms":"1539202764000"}}"#) {
+            StreamMessage::Limit(l) => assert_eq!(l.track, 1234),
+            msg => panic!("expected Limit, got {:?}", msg),
+        }
+        match parse(
+            r#"{"warning":{"code":"FALLING_BEHIND","message":"Your connection is falling behind","percent_full":60}}"#,
+        ) {
+            StreamMessage::StallWarning(w) => assert_eq!(w.percent_full, 60),
+            msg => panic!("expected StallWarning, got {:?}", msg),
+        }
+        match parse(r#"{"disconnect":{"code":4,"stream_name":"<name>","reason":"duplicate stream"}}"#) {
+            StreamMessage::Disconnect(d) => assert_eq!(d.code, 4),
+            msg => panic!("expected Disconnect, got {:?}", msg),
+        }
+        match parse(r#"{"user_withheld":{"id":123,"withheld_in_countries":["DE","AR"]}}"#) {
+            StreamMessage::UserWithheld(w) => assert_eq!(w.withheld_in_countries, ["DE", "AR"]),
+            msg => panic!("expected UserWithheld, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn payload() {
+        let msg = parse(
+            r#"{"data":{"id":"1067094924124872705","text":"Just getting started with Twitter APIs?",
+            "author_id":"2244994945","created_at":"2018-11-26T16:37:10.000Z"},
+            "includes":{"users":[{"id":"2244994945","name":"Twitter Dev","username":"TwitterDev"}]},
+            "matching_rules":[{"id":"1166895166390583299","tag":"twitter"}]}"#,
+        );
+
+        match msg {
+            StreamMessage::Payload(payload) => {
+                assert_eq!(payload.data.id, "1067094924124872705");
+                assert_eq!(payload.data.created_at.unwrap().timestamp(), 1543250230);
+                assert_eq!(payload.includes.users[0].username, "TwitterDev");
+                assert!(payload.includes.tweets.is_empty());
+                assert_eq!(payload.matching_rules[0].tag.as_ref().unwrap(), "twitter");
+            }
+            msg => panic!("expected Payload, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn unknown() {
+        let json = r#"{"friends":[1,2,3]}"#;
+        match parse(json) {
+            StreamMessage::Unknown(raw) => assert_eq!(&*raw, json),
+            msg => panic!("expected Unknown, got {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        for &json in &[
+            r#"{"limit":{"track":"many"}}"#,
+            r#"{"delete":{}}"#,
+            r#"{"id":1,"text":"no created_at nor user"}"#,
+            r#"{"data":[]}"#,
+        ] {
+            match parse(json) {
+                StreamMessage::Unknown(raw) => assert_eq!(&*raw, json),
+                msg => panic!("expected Unknown for {}, got {:?}", json, msg),
+            }
+        }
+
+        for &json in &["[1,2,3]", "{\"id\":"] {
+            let line = string::String::try_from(Bytes::from_static(json.as_bytes())).unwrap();
+            match StreamMessage::from_line(line) {
+                Err(Error::DeserializeError(_)) => (),
+                r => panic!("expected DeserializeError for {}, got {:?}", json, r),
+            }
+        }
+    }
+}
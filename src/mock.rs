@@ -0,0 +1,414 @@
+//! A local server speaking the Streaming API protocol, for tests and offline development.
+//!
+//! This module is only available with the `mock` feature.
+//!
+//! A `MockServer` listens on a random port of the loopback interface and answers each incoming
+//! connection with the next scripted `MockResponse`: CRLF-delimited JSON messages, keep-alive
+//! blank lines, stalls, error statuses and abrupt disconnects. Once the script is exhausted,
+//! connections are accepted but never receive any data.
+//!
+//! Every request must carry an OAuth 1.0a or bearer token `Authorization` header. Requests
+//! without one, or with other credentials than the ones given to `MockServerBuilder::credentials`
+//! or `MockServerBuilder::bearer`, are answered with `401 Unauthorized` and do not consume a
+//! scripted response. All requests are recorded
+//! and can be inspected with `MockServer::requests`.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::filters::RequestMethod;
+//! use khipu::mock::{MockResponse, MockServer};
+//! use khipu::{Glyph, StreamBuilder};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let server = MockServer::builder()
+//!     .credentials("consumer_key", "access_key")
+//!     .respond(
+//!         MockResponse::new()
+//!             .message(r#"{"limit":{"track":42}}"#)
+//!             .keep_alive()
+//!             .disconnect(),
+//!     )
+//!     .start()
+//!     .unwrap();
+//!
+//! let glyph = Glyph::new("consumer_key", "consumer_secret", "access_key", "access_secret");
+//! let endpoint = server.uri("/1.1/statuses/filter.json");
+//! let stream = StreamBuilder::custom(RequestMethod::POST, endpoint, glyph)
+//!     .track("rustlang")
+//!     .listen_with_client(&hyper::Client::new())
+//!     .await
+//!     .unwrap();
+//!
+//! let lines: Vec<_> = stream.collect().await;
+//! assert_eq!(lines.len(), 2);
+//! assert!(lines[1].is_err());
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_compression::stream::{BrotliEncoder, GzipEncoder, ZlibEncoder};
+use bytes::Bytes;
+use futures_util::future::{self, FutureExt};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode, Uri};
+use tokio::sync::oneshot;
+
+use crate::error::Error;
+
+/// A handle to a running mock server. The server is shut down when the handle is dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+/// A builder for `MockServer`.
+#[derive(Clone, Debug, Default)]
+pub struct MockServerBuilder {
+    responses: Vec<MockResponse>,
+    credentials: Option<(String, String)>,
+    bearer: Option<String>,
+}
+
+/// A scripted response to a single connection.
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    encoding: Option<Encoding>,
+    parts: Vec<Part>,
+}
+
+/// A content encoding applied to the body of a `MockResponse`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// `Content-Encoding: br`
+    Brotli,
+    /// `Content-Encoding: gzip`
+    Gzip,
+    /// `Content-Encoding: deflate`, i.e. zlib-wrapped deflate
+    Deflate,
+}
+
+/// A request received by a `MockServer`.
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+#[derive(Clone, Debug)]
+enum Part {
+    Data(Bytes),
+    Stall(Duration),
+    Disconnect,
+}
+
+struct State {
+    responses: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<MockRequest>>,
+    credentials: Option<(String, String)>,
+    bearer: Option<String>,
+}
+
+const UNAUTHORIZED: &str = r#"{"errors":[{"code":32,"message":"Could not authenticate you."}]}"#;
+
+impl MockServer {
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the URI of `path` on the server, to be passed to `StreamBuilder::custom`.
+    pub fn uri(&self, path: &str) -> Uri {
+        format!("http://{}{}", self.addr, path).parse().unwrap()
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the number of scripted responses which have not been sent yet.
+    pub fn remaining(&self) -> usize {
+        self.state.responses.lock().unwrap().len()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+impl MockServerBuilder {
+    /// Append a response to the script. Each connection receives the next response.
+    pub fn respond(&mut self, response: MockResponse) -> &mut Self {
+        self.responses.push(response);
+        self
+    }
+
+    /// Only accept OAuth 1.0a requests signed with the given consumer key and access token.
+    ///
+    /// By default, any request with an OAuth `Authorization` header is accepted.
+    pub fn credentials(&mut self, consumer_key: &str, token: &str) -> &mut Self {
+        self.credentials = Some((consumer_key.to_string(), token.to_string()));
+        self
+    }
+
+    /// Only accept bearer token requests carrying `token`.
+    ///
+    /// By default, any request with a bearer token is accepted.
+    pub fn bearer(&mut self, token: &str) -> &mut Self {
+        self.bearer = Some(token.to_string());
+        self
+    }
+
+    /// Start the server on the current Tokio runtime.
+    pub fn start(&self) -> Result<MockServer, Error> {
+        let state = Arc::new(State {
+            responses: Mutex::new(self.responses.iter().cloned().collect()),
+            requests: Mutex::new(Vec::new()),
+            credentials: self.credentials.clone(),
+            bearer: self.bearer.clone(),
+        });
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            future::ok::<_, Infallible>(service_fn(move |req| handle(req, state.clone())))
+        });
+
+        let server = Server::try_bind(&([127, 0, 0, 1], 0).into())?.serve(make_service);
+        let addr = server.local_addr();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(future::select(Box::pin(server), rx).map(|_| ()));
+
+        Ok(MockServer {
+            addr,
+            state,
+            shutdown: Some(tx),
+        })
+    }
+}
+
+impl MockResponse {
+    /// Creates an empty `200 OK` response.
+    pub fn new() -> Self {
+        MockResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            encoding: None,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Creates an empty response with the given status, e.g. `420 Enhance Your Calm`.
+    pub fn error(status: StatusCode) -> Self {
+        MockResponse {
+            status,
+            ..MockResponse::new()
+        }
+    }
+
+    /// Add a header to the response, e.g. `x-rate-limit-remaining`.
+    pub fn header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.append(name, HeaderValue::from_static(value));
+        self
+    }
+
+    /// Send a JSON message followed by CRLF.
+    pub fn message(self, json: &str) -> Self {
+        self.raw(format!("{}\r\n", json))
+    }
+
+    /// Send a blank line, as Twitter does every 30 seconds to keep the connection alive.
+    pub fn keep_alive(self) -> Self {
+        self.raw("\r\n")
+    }
+
+    /// Send arbitrary bytes, e.g. half of a message.
+    pub fn raw(mut self, data: impl Into<Bytes>) -> Self {
+        self.parts.push(Part::Data(data.into()));
+        self
+    }
+
+    /// Stop sending data for `duration`.
+    pub fn stall(mut self, duration: Duration) -> Self {
+        self.parts.push(Part::Stall(duration));
+        self
+    }
+
+    /// Abort the connection without properly terminating the response.
+    /// Parts added after this one are never sent.
+    pub fn disconnect(mut self) -> Self {
+        self.parts.push(Part::Disconnect);
+        self
+    }
+
+    /// Encode the body with `encoding`, if the request accepts it.
+    ///
+    /// Note that compressed data may be buffered by the encoder and delivered in batches.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    fn into_response(self, accept_encoding: &HeaderMap) -> Response<Body> {
+        let body = stream::iter(self.parts).filter_map(|part| async move {
+            match part {
+                Part::Data(data) => Some(Ok(data)),
+                Part::Stall(duration) => {
+                    tokio_timer::delay_for(duration).await;
+                    None
+                }
+                Part::Disconnect => Some(Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "mock server disconnected",
+                ))),
+            }
+        });
+
+        let encoding = self
+            .encoding
+            .filter(|e| accepts(accept_encoding, e.token()));
+        let body = match encoding {
+            Some(Encoding::Brotli) => Body::wrap_stream(BrotliEncoder::new(body)),
+            Some(Encoding::Gzip) => Body::wrap_stream(GzipEncoder::new(body)),
+            Some(Encoding::Deflate) => Body::wrap_stream(ZlibEncoder::new(body)),
+            None => Body::wrap_stream(body),
+        };
+
+        let mut response = Response::new(body);
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        if let Some(e) = encoding {
+            response
+                .headers_mut()
+                .insert(CONTENT_ENCODING, HeaderValue::from_static(e.token()));
+        }
+        response
+    }
+}
+
+impl Default for MockResponse {
+    fn default() -> Self {
+        MockResponse::new()
+    }
+}
+
+impl Encoding {
+    fn token(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+async fn handle(req: Request<Body>, state: Arc<State>) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = req.into_parts();
+    let body = body
+        .try_concat()
+        .await
+        .map(|chunk| chunk.into_bytes())
+        .unwrap_or_default();
+    let authorized = is_authorized(&parts.headers, &state);
+
+    state.requests.lock().unwrap().push(MockRequest {
+        method: parts.method,
+        uri: parts.uri,
+        headers: parts.headers.clone(),
+        body,
+    });
+
+    if !authorized {
+        let mut response = Response::new(Body::from(UNAUTHORIZED));
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        return Ok(response);
+    }
+
+    let next = state.responses.lock().unwrap().pop_front();
+    Ok(match next {
+        Some(response) => response.into_response(&parts.headers),
+        None => Response::new(Body::wrap_stream(stream::pending::<io::Result<Bytes>>())),
+    })
+}
+
+/// Checks that `headers` holds either an OAuth 1.0a `Authorization` header with all the required
+/// parameters or a bearer token and, if given, the expected credentials.
+fn is_authorized(headers: &HeaderMap, state: &State) -> bool {
+    let header = match headers.get(AUTHORIZATION).and_then(|h| h.to_str().ok()) {
+        Some(h) if h.starts_with("OAuth ") => &h["OAuth ".len()..],
+        Some(h) if h.starts_with("Bearer ") => {
+            let token = &h["Bearer ".len()..];
+            return !token.is_empty() && state.bearer.as_ref().map_or(true, |t| t == token);
+        }
+        _ => return false,
+    };
+
+    let params: Vec<(&str, &str)> = header
+        .split(',')
+        .filter_map(|param| {
+            let mut kv = param.trim().splitn(2, '=');
+            let k = kv.next()?;
+            let v = kv.next()?.trim_matches('"');
+            Some((k, v))
+        })
+        .collect();
+    let param = |name| params.iter().find(|&&(k, _)| k == name).map(|&(_, v)| v);
+
+    let required = [
+        "oauth_consumer_key",
+        "oauth_nonce",
+        "oauth_signature",
+        "oauth_signature_method",
+        "oauth_timestamp",
+    ];
+    if required
+        .iter()
+        .any(|&name| param(name).map_or(true, str::is_empty))
+    {
+        return false;
+    }
+
+    match state.credentials {
+        Some((ref consumer_key, ref token)) => {
+            param("oauth_consumer_key") == Some(consumer_key.as_str())
+                && param("oauth_token") == Some(token.as_str())
+        }
+        None => true,
+    }
+}
+
+fn accepts(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .any(|coding| {
+            let coding = coding.split(';').next().unwrap_or("").trim();
+            coding.eq_ignore_ascii_case(token)
+        })
+}
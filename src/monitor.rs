@@ -0,0 +1,272 @@
+//! Monitoring of stall warnings and rate limit notices.
+//!
+//! Twitter tells a client that it is falling behind with stall warnings, which are only sent if
+//! `StreamBuilder::stall_warnings` is enabled, and that Tweets matching the filter were not
+//! delivered with limit notices. A `Monitor` keeps track of both across one or more
+//! connections: wrap the messages of each connection with `Messages::monitor` and read the
+//! totals from the `Monitor`, e.g. to feed a dashboard.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::monitor::Monitor;
+//! use khipu::{Glyph, StreamBuilder};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let glyph: Glyph = unimplemented!();
+//! let monitor = Monitor::new();
+//! monitor.on_stall(60, |warning| eprintln!("falling behind: {}", warning.message));
+//!
+//! let stream = StreamBuilder::filter(glyph)
+//!     .track("rustlang")
+//!     .stall_warnings(true)
+//!     .listen()
+//!     .unwrap()
+//!     .await
+//!     .unwrap();
+//! let mut messages = stream.messages().monitor(&monitor);
+//!
+//! while let Some(msg) = messages.try_next().await.unwrap() {
+//!     // ...
+//! #   drop(msg);
+//! }
+//! println!("{} Tweets were not delivered", monitor.dropped());
+//! # }
+//! ```
+
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+
+use crate::error::Error;
+use crate::message::{Messages, StallWarning, StreamMessage};
+
+/// A handle to statistics shared by the streams it monitors.
+///
+/// Clones of a `Monitor` share the same statistics and hook.
+#[derive(Clone, Default)]
+pub struct Monitor {
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    stats: Stats,
+    hook: Option<(u8, Hook)>,
+}
+
+type Hook = Arc<dyn Fn(&StallWarning) + Send + Sync>;
+
+/// A snapshot of the statistics of a `Monitor`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The total number of Tweets which matched the filter but were not delivered.
+    pub dropped: u64,
+    /// The number of limit notices received.
+    pub limit_notices: u64,
+    /// The number of stall warnings received.
+    pub stall_warnings: u64,
+    /// The `percent_full` of the last stall warning, if any.
+    pub percent_full: Option<u8>,
+}
+
+/// A stream adapter returned by `Messages::monitor`.
+/// It yields the messages of the underlying stream unchanged.
+pub struct Monitored<S> {
+    inner: S,
+    monitor: Monitor,
+    // The `track` of the last limit notice, which counts the undelivered Tweets since the
+    // connection was opened.
+    track: u64,
+    percent_full: u8,
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set a function to be called with the stall warnings whose `percent_full` reaches
+    /// `threshold`. It is called once each time the queue of a connection fills past the
+    /// threshold, not for every warning above it.
+    ///
+    /// The function replaces any previous one, for all the clones of this `Monitor`.
+    pub fn on_stall<F>(&self, threshold: u8, hook: F) -> &Self
+    where
+        F: Fn(&StallWarning) + Send + Sync + 'static,
+    {
+        self.shared.lock().unwrap().hook = Some((threshold, Arc::new(hook)));
+        self
+    }
+
+    /// Returns the total number of Tweets which matched the filter but were not delivered.
+    pub fn dropped(&self) -> u64 {
+        self.stats().dropped
+    }
+
+    /// Returns a snapshot of the statistics.
+    pub fn stats(&self) -> Stats {
+        self.shared.lock().unwrap().stats
+    }
+
+    /// Wrap a stream of messages from a single connection.
+    pub fn monitor<S>(&self, stream: S) -> Monitored<S>
+    where
+        S: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+    {
+        Monitored {
+            inner: stream,
+            monitor: self.clone(),
+            track: 0,
+            percent_full: 0,
+        }
+    }
+}
+
+impl fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shared = self.shared.lock().unwrap();
+        f.debug_struct("Monitor")
+            .field("stats", &shared.stats)
+            .field("threshold", &shared.hook.as_ref().map(|&(t, _)| t))
+            .finish()
+    }
+}
+
+impl<S> Monitored<S> {
+    /// Returns the `Monitor` updated by this stream.
+    pub fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn observe(&mut self, msg: &StreamMessage) {
+        match *msg {
+            StreamMessage::Limit(ref limit) => {
+                let stats = &mut self.monitor.shared.lock().unwrap().stats;
+                stats.limit_notices += 1;
+                // Notices may arrive out of order, so only count increases.
+                if limit.track > self.track {
+                    stats.dropped += limit.track - self.track;
+                    self.track = limit.track;
+                }
+            }
+            StreamMessage::StallWarning(ref warning) => {
+                let hook = {
+                    let mut shared = self.monitor.shared.lock().unwrap();
+                    shared.stats.stall_warnings += 1;
+                    shared.stats.percent_full = Some(warning.percent_full);
+                    shared.hook.clone()
+                };
+                // The lock is released so that the hook may use the `Monitor`.
+                if let Some((threshold, hook)) = hook {
+                    if self.percent_full < threshold && warning.percent_full >= threshold {
+                        hook(warning);
+                    }
+                }
+                self.percent_full = warning.percent_full;
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<S> Stream for Monitored<S>
+where
+    S: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+{
+    type Item = Result<StreamMessage, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = ready_some!(self.inner.poll_next_unpin(cx));
+        if let Ok(ref msg) = item {
+            self.observe(msg);
+        }
+        Poll::Ready(Some(item))
+    }
+}
+
+impl Messages {
+    /// Turn the stream into one updating the statistics of `monitor` as messages go by.
+    pub fn monitor(self, monitor: &Monitor) -> Monitored<Self> {
+        monitor.monitor(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures_executor::block_on_stream;
+    use futures_util::stream;
+
+    use crate::message::Limit;
+
+    fn limit(track: u64) -> Result<StreamMessage, Error> {
+        Ok(StreamMessage::Limit(Limit { track }))
+    }
+
+    fn warning(percent_full: u8) -> Result<StreamMessage, Error> {
+        Ok(StreamMessage::StallWarning(StallWarning {
+            code: "FALLING_BEHIND".to_string(),
+            message: String::new(),
+            percent_full,
+        }))
+    }
+
+    #[test]
+    fn dropped() {
+        let monitor = Monitor::new();
+
+        let first = monitor.monitor(stream::iter(vec![limit(5), limit(12), limit(9)]));
+        assert_eq!(block_on_stream(first).count(), 3);
+        assert_eq!(monitor.dropped(), 12);
+
+        // `track` starts over with each connection.
+        let second = monitor.monitor(stream::iter(vec![limit(3)]));
+        assert_eq!(block_on_stream(second).count(), 1);
+
+        let stats = monitor.stats();
+        assert_eq!(stats.dropped, 15);
+        assert_eq!(stats.limit_notices, 4);
+    }
+
+    #[test]
+    fn on_stall() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let monitor = Monitor::new();
+        // The hook is shared with clones made before it was set.
+        let clone = monitor.clone();
+        let c = calls.clone();
+        monitor.on_stall(60, move |_| {
+            c.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let warnings = vec![
+            warning(40),
+            warning(65),
+            warning(80),
+            warning(50),
+            warning(60),
+        ];
+        let s = clone.monitor(stream::iter(warnings));
+        assert_eq!(block_on_stream(s).count(), 5);
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let stats = monitor.stats();
+        assert_eq!(stats.stall_warnings, 5);
+        assert_eq!(stats.percent_full, Some(60));
+    }
+}
@@ -0,0 +1,209 @@
+//! Rate limit information returned by the Twitter API.
+//!
+//! Responses of the REST API carry the `x-rate-limit-limit`, `x-rate-limit-remaining` and
+//! `x-rate-limit-reset` headers, and responses to clients connecting too often may carry a
+//! `Retry-After` header. `RateLimitStatus` holds whichever of them were present. It is available
+//! from `TwitterStream::rate_limit` once connected, and from `Error::RateLimit` when the server
+//! answered `420 Enhance Your Calm` or `429 Too Many Requests`.
+
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+use chrono::format::Parsed;
+use chrono::{DateTime, Utc};
+use hyper::header::{HeaderMap, RETRY_AFTER};
+
+use crate::error::Error;
+
+const LIMIT: &str = "x-rate-limit-limit";
+const REMAINING: &str = "x-rate-limit-remaining";
+const RESET: &str = "x-rate-limit-reset";
+
+/// The rate limit headers of a response.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// The number of requests allowed in the current window.
+    pub limit: Option<u32>,
+    /// The number of requests left in the current window.
+    pub remaining: Option<u32>,
+    /// When the current window ends.
+    pub reset: Option<DateTime<Utc>>,
+    /// How long to wait before retrying, from `Retry-After`.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitStatus {
+    /// Parses the rate limit headers of a response. Missing headers are left as `None`.
+    ///
+    /// Fails on the first malformed header. See `from_headers_lossy` to keep the well-formed
+    /// ones instead.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, Error> {
+        let (status, mut errors) = RateLimitStatus::from_headers_lossy(headers);
+        if errors.is_empty() {
+            Ok(status)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Parses the rate limit headers of a response, each on its own, so that a malformed header
+    /// does not hide the others. Missing and malformed headers are left as `None`, and the errors
+    /// of the malformed ones are returned along with the status.
+    pub fn from_headers_lossy(headers: &HeaderMap) -> (Self, Vec<Error>) {
+        let mut errors = Vec::new();
+        let status = RateLimitStatus {
+            limit: field(headers, LIMIT, |v| Ok(v.parse()?), &mut errors),
+            remaining: field(headers, REMAINING, |v| Ok(v.parse()?), &mut errors),
+            reset: field(headers, RESET, parse_reset, &mut errors),
+            retry_after: field(
+                headers,
+                RETRY_AFTER.as_str(),
+                parse_retry_after,
+                &mut errors,
+            ),
+        };
+        (status, errors)
+    }
+
+    /// Returns how long to wait before the next request, if the headers call for waiting:
+    /// `retry_after` if present, or the time left until `reset` if no requests remain.
+    pub fn wait_time(&self) -> Option<Duration> {
+        if self.retry_after.is_some() {
+            return self.retry_after;
+        }
+        match (self.remaining, self.reset) {
+            (Some(0), Some(reset)) => Some((reset - Utc::now()).to_std().unwrap_or_default()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RateLimitStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.remaining, self.limit) {
+            (Some(remaining), Some(limit)) => write!(f, "{}/{} requests left", remaining, limit)?,
+            _ => f.write_str("rate limited")?,
+        }
+        if let Some(reset) = self.reset {
+            write!(f, ", resets at {}", reset)?;
+        }
+        if let Some(retry_after) = self.retry_after {
+            write!(f, ", retry after {}s", retry_after.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Result<Option<&'a str>, Error> {
+    match headers.get(name) {
+        Some(value) => Ok(Some(value.to_str()?.trim())),
+        None => Ok(None),
+    }
+}
+
+/// Parses the header `name` if present, recording the error in `errors` if it is malformed.
+fn field<T, F>(headers: &HeaderMap, name: &str, parse: F, errors: &mut Vec<Error>) -> Option<T>
+where
+    F: FnOnce(&str) -> Result<T, Error>,
+{
+    match header(headers, name).and_then(|value| value.map(parse).transpose()) {
+        Ok(value) => value,
+        Err(e) => {
+            errors.push(e);
+            None
+        }
+    }
+}
+
+/// Parses a Unix timestamp. Timestamps out of the range of `DateTime` are an error.
+fn parse_reset(value: &str) -> Result<DateTime<Utc>, Error> {
+    let mut parsed = Parsed::new();
+    parsed.set_timestamp(value.parse()?)?;
+    Ok(parsed.to_datetime_with_timezone(&Utc)?)
+}
+
+/// Parses either a number of seconds or an HTTP-date.
+fn parse_retry_after(value: &str) -> Result<Duration, Error> {
+    if value.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(Duration::from_secs(value.parse()?));
+    }
+    let date = DateTime::parse_from_rfc2822(value)?;
+    Ok((date.with_timezone(&Utc) - Utc::now())
+        .to_std()
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            RateLimitStatus::from_headers(&headers).unwrap(),
+            RateLimitStatus::default()
+        );
+
+        headers.insert(LIMIT, HeaderValue::from_static("15"));
+        headers.insert(REMAINING, HeaderValue::from_static("0"));
+        headers.insert(RESET, HeaderValue::from_static("1539202764"));
+        let status = RateLimitStatus::from_headers(&headers).unwrap();
+        assert_eq!(status.limit, Some(15));
+        assert_eq!(status.remaining, Some(0));
+        assert_eq!(status.reset.unwrap().timestamp(), 1539202764);
+        // The window is long over.
+        assert_eq!(status.wait_time(), Some(Duration::from_secs(0)));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        let status = RateLimitStatus::from_headers(&headers).unwrap();
+        assert_eq!(status.wait_time(), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        let status = RateLimitStatus::from_headers(&headers).unwrap();
+        assert_eq!(status.retry_after, Some(Duration::from_secs(0)));
+
+        headers.insert(RESET, HeaderValue::from_str(&i64::MAX.to_string()).unwrap());
+        match RateLimitStatus::from_headers(&headers) {
+            Err(Error::TimestampParseError(_)) => (),
+            r => panic!("expected TimestampParseError, got {:?}", r),
+        }
+
+        headers.insert(REMAINING, HeaderValue::from_static("many"));
+        match RateLimitStatus::from_headers(&headers) {
+            Err(Error::HeaderConvertError(_)) => (),
+            r => panic!("expected HeaderConvertError, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn from_headers_lossy() {
+        let mut headers = HeaderMap::new();
+        headers.insert(LIMIT, HeaderValue::from_static("15"));
+        headers.insert(RESET, HeaderValue::from_static("soon"));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+
+        assert!(RateLimitStatus::from_headers(&headers).is_err());
+
+        let (status, errors) = RateLimitStatus::from_headers_lossy(&headers);
+        assert_eq!(
+            status,
+            RateLimitStatus {
+                limit: Some(15),
+                remaining: None,
+                reset: None,
+                retry_after: Some(Duration::from_secs(120)),
+            }
+        );
+        assert_eq!(status.wait_time(), Some(Duration::from_secs(120)));
+        match *errors {
+            [Error::HeaderConvertError(_)] => (),
+            ref errors => panic!("expected a HeaderConvertError, got {:?}", errors),
+        }
+    }
+}
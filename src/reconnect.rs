@@ -0,0 +1,564 @@
+//! Automatic reconnection to the Streaming API.
+//!
+//! Streaming connections are regularly dropped by the network or by Twitter. `ReconnectingStream`
+//! wraps a function opening a connection (usually `StreamBuilder::listen_with_client`) and opens
+//! a new connection whenever the current one fails, waiting between attempts as recommended in
+//! the [official guide][1]:
+//!
+//! * back off linearly for network errors, starting at 250 milliseconds, up to 16 seconds,
+//! * back off exponentially for HTTP errors, starting at 5 seconds, up to 320 seconds,
+//! * back off exponentially for HTTP 420 and 429 errors, starting at 1 minute, or longer if
+//!   the `RateLimitStatus` of the response asks for it.
+//!
+//! The waiting times are reset as soon as a connection succeeds. Errors which cannot be fixed by
+//! retrying, such as 401 Unauthorized, end the stream (see `Error::is_retryable`).
+//!
+//! Waiting is delegated to a `Clock`, so that the policy can be tested with a `MockClock`
+//! instead of actually sleeping.
+//!
+//! [1]: https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/connecting
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::reconnect::{Event, ReconnectPolicy};
+//! use khipu::{Glyph, StreamBuilder};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let glyph: Glyph = unimplemented!();
+//! let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new().unwrap());
+//! let mut builder = StreamBuilder::sample(glyph);
+//! builder.stall_warnings(true);
+//!
+//! let mut stream = builder.listen_reconnecting_with_client(&client, ReconnectPolicy::default());
+//! while let Some(event) = stream.next().await {
+//!     match event.unwrap() {
+//!         Event::Connected => println!("connected"),
+//!         Event::Message(json) => println!("{}", json),
+//!         Event::Reconnecting { error, delay, .. } => {
+//!             println!("disconnected ({:?}), reconnecting in {:?}", error, delay)
+//!         }
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::cmp;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use futures_util::future::{self, FutureExt};
+use futures_util::{ready, StreamExt};
+
+use crate::error::Error;
+
+/// Opens connections for a `ReconnectingStream`.
+///
+/// This is implemented for any `FnMut() -> impl Future<Output = Result<S, Error>>`.
+pub trait Connector {
+    /// The future resolving to a connection.
+    type Future: Future<Output = Result<Self::Stream, Error>>;
+    /// The connection.
+    type Stream;
+
+    /// Starts opening a new connection.
+    fn connect(&mut self) -> Self::Future;
+}
+
+/// Creates futures completing after a given duration.
+pub trait Clock {
+    /// The future returned by `delay`.
+    type Delay: Future<Output = ()>;
+
+    /// Returns a future which completes after `duration`.
+    fn delay(&self, duration: Duration) -> Self::Delay;
+}
+
+/// A `Clock` backed by the timer of the Tokio runtime.
+#[cfg(feature = "runtime")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+/// A `Clock` whose delays complete immediately, recording the requested durations.
+///
+/// Clones share the same record.
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    delays: Arc<Mutex<Vec<Duration>>>,
+}
+
+/// How the waiting time grows with the number of consecutive failed attempts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// Wait `initial`, then `initial + step`, `initial + 2 * step`, ... up to `max`.
+    Linear {
+        initial: Duration,
+        step: Duration,
+        max: Duration,
+    },
+    /// Wait `initial`, then twice as long on each attempt, up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+/// The backoff applied to each kind of error.
+///
+/// The default values are the ones recommended by Twitter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// The backoff for network errors, timeouts and connections closed by the server.
+    /// Defaults to linear, from 250 milliseconds up to 16 seconds.
+    pub network: Backoff,
+    /// The backoff for HTTP errors. Defaults to exponential, from 5 seconds up to 320 seconds.
+    pub http: Backoff,
+    /// The backoff for HTTP 420 and 429 errors. Defaults to exponential, from 1 minute up to
+    /// 15 minutes, the length of a rate limit window. The delay is extended to the
+    /// `RateLimitStatus::wait_time` of `Error::RateLimit`.
+    pub rate_limit: Backoff,
+    /// The number of consecutive failed attempts, counting the first connection attempt, after
+    /// which the stream gives up and yields the last error, or `Error::MaxAttempts` if the
+    /// server closed the connection. Connections closed before delivering any message count as
+    /// failed attempts. Defaults to `None`, i.e. retry forever.
+    pub max_attempts: Option<u32>,
+}
+
+/// An event yielded by a `ReconnectingStream`.
+#[derive(Debug)]
+pub enum Event<T> {
+    /// A connection was established.
+    Connected,
+    /// A message was received on the current connection.
+    Message(T),
+    /// A connection attempt failed or the current connection was lost. A new attempt will be
+    /// made after `delay`.
+    Reconnecting {
+        /// The error which caused the disconnection, or `None` if the server closed the
+        /// connection.
+        error: Option<Error>,
+        /// The number of consecutive failed attempts, starting at 1.
+        attempt: u32,
+        /// The time to wait before the next attempt.
+        delay: Duration,
+    },
+}
+
+/// A stream adapter returned by `StreamBuilder::listen_reconnecting_with_client`.
+///
+/// It yields `Event`s and only yields an error, then ends, when the error cannot be fixed by
+/// reconnecting or when `ReconnectPolicy::max_attempts` is reached. In the latter case, the
+/// error is the one which caused the last failure, or `Error::MaxAttempts` if the server closed
+/// the connection.
+pub struct ReconnectingStream<K: Connector, C: Clock> {
+    connector: K,
+    policy: ReconnectPolicy,
+    clock: C,
+    state: State<K::Future, K::Stream, C::Delay>,
+    failures: Failures,
+}
+
+enum State<F, S, D> {
+    Connecting(F),
+    Connected(S),
+    Waiting(D),
+    Done,
+}
+
+#[derive(Default)]
+struct Failures {
+    network: u32,
+    http: u32,
+    rate_limit: u32,
+    consecutive: u32,
+}
+
+#[derive(Debug, PartialEq)]
+enum ErrorKind {
+    Network,
+    Http,
+    RateLimit,
+    Fatal,
+}
+
+impl<F, Fut, S> Connector for F
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<S, Error>>,
+{
+    type Future = Fut;
+    type Stream = S;
+
+    fn connect(&mut self) -> Fut {
+        self()
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl Clock for TokioClock {
+    type Delay = tokio_timer::Delay;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        tokio_timer::delay_for(duration)
+    }
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock::default()
+    }
+
+    /// Returns the durations of all the delays requested so far, in order.
+    pub fn delays(&self) -> Vec<Duration> {
+        self.delays.lock().unwrap().clone()
+    }
+}
+
+impl Clock for MockClock {
+    type Delay = future::Ready<()>;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        self.delays.lock().unwrap().push(duration);
+        future::ready(())
+    }
+}
+
+impl Backoff {
+    /// Returns the time to wait before the next attempt, after `failures` consecutive failed
+    /// attempts.
+    pub fn delay(&self, failures: u32) -> Duration {
+        match *self {
+            Backoff::Linear { initial, step, max } => step
+                .checked_mul(failures)
+                .and_then(|d| d.checked_add(initial))
+                .map_or(max, |d| cmp::min(d, max)),
+            Backoff::Exponential { initial, max } => 1u32
+                .checked_shl(failures)
+                .and_then(|factor| initial.checked_mul(factor))
+                .map_or(max, |d| cmp::min(d, max)),
+        }
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            network: Backoff::Linear {
+                initial: Duration::from_millis(250),
+                step: Duration::from_millis(250),
+                max: Duration::from_secs(16),
+            },
+            http: Backoff::Exponential {
+                initial: Duration::from_secs(5),
+                max: Duration::from_secs(320),
+            },
+            rate_limit: Backoff::Exponential {
+                initial: Duration::from_secs(60),
+                max: Duration::from_secs(15 * 60),
+            },
+            max_attempts: None,
+        }
+    }
+}
+
+impl<K: Connector, C: Clock> ReconnectingStream<K, C> {
+    /// Creates a stream opening connections with `connector` and waiting between attempts
+    /// with `clock`. The first connection attempt is started immediately.
+    pub fn new(mut connector: K, policy: ReconnectPolicy, clock: C) -> Self {
+        let state = State::Connecting(connector.connect());
+        ReconnectingStream {
+            connector,
+            policy,
+            clock,
+            state,
+            failures: Failures::default(),
+        }
+    }
+
+    /// Returns the reconnection policy.
+    pub fn policy(&self) -> &ReconnectPolicy {
+        &self.policy
+    }
+
+    /// Returns the number of consecutive failed attempts so far.
+    pub fn failed_attempts(&self) -> u32 {
+        self.failures.consecutive
+    }
+
+    fn reconnect<T>(&mut self, error: Option<Error>) -> Option<Result<Event<T>, Error>> {
+        let (backoff, failures) = match classify(error.as_ref()) {
+            ErrorKind::Network => (self.policy.network, &mut self.failures.network),
+            ErrorKind::Http => (self.policy.http, &mut self.failures.http),
+            ErrorKind::RateLimit => (self.policy.rate_limit, &mut self.failures.rate_limit),
+            ErrorKind::Fatal => {
+                self.state = State::Done;
+                return error.map(Err);
+            }
+        };
+
+        self.failures.consecutive += 1;
+        if let Some(max) = self.policy.max_attempts {
+            if self.failures.consecutive >= max {
+                self.state = State::Done;
+                return Some(Err(error.unwrap_or(Error::MaxAttempts(max))));
+            }
+        }
+
+        // Wait at least as long as the server asked to.
+        let wait = match error {
+            Some(Error::RateLimit(ref status)) => status.wait_time(),
+            _ => None,
+        };
+        let delay = cmp::max(backoff.delay(*failures), wait.unwrap_or_default());
+        *failures += 1;
+        self.state = State::Waiting(self.clock.delay(delay));
+
+        Some(Ok(Event::Reconnecting {
+            error,
+            attempt: self.failures.consecutive,
+            delay,
+        }))
+    }
+}
+
+impl<K, C, T> Stream for ReconnectingStream<K, C>
+where
+    K: Connector + Unpin,
+    K::Future: Unpin,
+    K::Stream: Stream<Item = Result<T, Error>> + Unpin,
+    C: Clock + Unpin,
+    C::Delay: Unpin,
+{
+    type Item = Result<Event<T>, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match this.state {
+                State::Connecting(ref mut fut) => match ready!(fut.poll_unpin(cx)) {
+                    Ok(stream) => {
+                        // The attempt only counts as a success once a message is received.
+                        this.failures = Failures {
+                            consecutive: this.failures.consecutive,
+                            ..Failures::default()
+                        };
+                        this.state = State::Connected(stream);
+                        return Poll::Ready(Some(Ok(Event::Connected)));
+                    }
+                    Err(e) => return Poll::Ready(this.reconnect(Some(e))),
+                },
+                State::Connected(ref mut stream) => match ready!(stream.poll_next_unpin(cx)) {
+                    Some(Ok(msg)) => {
+                        this.failures.consecutive = 0;
+                        return Poll::Ready(Some(Ok(Event::Message(msg))));
+                    }
+                    Some(Err(e)) => return Poll::Ready(this.reconnect(Some(e))),
+                    None => return Poll::Ready(this.reconnect(None)),
+                },
+                State::Waiting(ref mut delay) => {
+                    ready!(delay.poll_unpin(cx));
+                    this.state = State::Connecting(this.connector.connect());
+                }
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+fn classify(error: Option<&Error>) -> ErrorKind {
+    let error = match error {
+        Some(error) => error,
+        // The stream ended without an error.
+        None => return ErrorKind::Network,
+    };
+
+    if error.is_rate_limit() {
+        ErrorKind::RateLimit
+    } else if !error.is_retryable() {
+        ErrorKind::Fatal
+    } else if error.status().is_some() {
+        ErrorKind::Http
+    } else {
+        ErrorKind::Network
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+
+    use bytes::Bytes;
+    use futures_executor::block_on_stream;
+    use futures_util::stream;
+    use hyper::header::HeaderMap;
+
+    use crate::filters::StatusCode;
+    use crate::rate_limit::RateLimitStatus;
+
+    type Connection = stream::Iter<std::vec::IntoIter<Result<u32, Error>>>;
+
+    fn http(code: u16) -> Error {
+        Error::Http(StatusCode::from_u16(code).unwrap(), Bytes::new())
+    }
+
+    fn connector(
+        attempts: Vec<Result<Vec<Result<u32, Error>>, Error>>,
+    ) -> impl FnMut() -> future::Ready<Result<Connection, Error>> {
+        let mut attempts: VecDeque<_> = attempts.into_iter().collect();
+        move || future::ready(attempts.pop_front().unwrap().map(stream::iter))
+    }
+
+    #[test]
+    fn backoff() {
+        let policy = ReconnectPolicy::default();
+        let millis = |b: Backoff, n| b.delay(n).as_millis() as u64;
+
+        assert_eq!(millis(policy.network, 0), 250);
+        assert_eq!(millis(policy.network, 3), 1_000);
+        assert_eq!(millis(policy.network, 100), 16_000);
+        assert_eq!(millis(policy.http, 0), 5_000);
+        assert_eq!(millis(policy.http, 2), 20_000);
+        assert_eq!(millis(policy.http, 40), 320_000);
+        assert_eq!(millis(policy.rate_limit, 1), 120_000);
+        assert_eq!(millis(policy.rate_limit, 5), 900_000);
+    }
+
+    #[test]
+    fn reconnect() {
+        let clock = MockClock::new();
+        let connector = connector(vec![
+            Err(http(503)),
+            Err(http(503)),
+            Ok(vec![
+                Ok(1),
+                Err(Error::InactivityTimeout(Duration::from_secs(90))),
+            ]),
+            Ok(vec![Ok(2)]),
+            Err(http(420)),
+            Err(Error::RateLimit(RateLimitStatus {
+                retry_after: Some(Duration::from_secs(200)),
+                ..RateLimitStatus::default()
+            })),
+            Err(http(401)),
+        ]);
+        let stream = ReconnectingStream::new(connector, ReconnectPolicy::default(), clock.clone());
+
+        let mut events = Vec::new();
+        let mut error = None;
+        for event in block_on_stream(stream) {
+            match event {
+                Ok(Event::Connected) => events.push("connected".to_string()),
+                Ok(Event::Message(n)) => events.push(n.to_string()),
+                Ok(Event::Reconnecting { attempt, .. }) => {
+                    events.push(format!("retry {}", attempt))
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+
+        assert_eq!(
+            events,
+            [
+                "retry 1",
+                "retry 2",
+                "connected",
+                "1",
+                "retry 1",
+                "connected",
+                "2",
+                "retry 1",
+                "retry 2",
+                "retry 3",
+            ]
+        );
+        match error {
+            Some(Error::Http(status, _)) => assert_eq!(status.as_u16(), 401),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert_eq!(
+            clock.delays(),
+            [
+                Duration::from_secs(5),
+                Duration::from_secs(10),
+                Duration::from_millis(250),
+                Duration::from_millis(250),
+                Duration::from_secs(60),
+                Duration::from_secs(200),
+            ]
+        );
+    }
+
+    #[test]
+    fn classify() {
+        let over_daily_limit = Error::from_response(
+            StatusCode::FORBIDDEN,
+            &HeaderMap::new(),
+            Bytes::from_static(br#"{"errors":[{"code":185,"message":""}]}"#),
+        );
+        assert_eq!(
+            super::classify(Some(&over_daily_limit)),
+            ErrorKind::RateLimit
+        );
+        assert_eq!(super::classify(Some(&http(420))), ErrorKind::RateLimit);
+        assert_eq!(super::classify(Some(&http(403))), ErrorKind::Fatal);
+        assert_eq!(super::classify(Some(&http(503))), ErrorKind::Http);
+        assert_eq!(
+            super::classify(Some(&Error::InactivityTimeout(Duration::from_secs(90)))),
+            ErrorKind::Network
+        );
+        assert_eq!(super::classify(None), ErrorKind::Network);
+    }
+
+    #[test]
+    fn max_attempts() {
+        let connector = connector(vec![
+            Err(Error::InactivityTimeout(Duration::from_secs(90))),
+            Err(http(500)),
+        ]);
+        let policy = ReconnectPolicy {
+            max_attempts: Some(2),
+            ..ReconnectPolicy::default()
+        };
+        let stream = ReconnectingStream::new(connector, policy, MockClock::new());
+
+        let events: Vec<_> = block_on_stream(stream).collect();
+        assert_eq!(events.len(), 2);
+        match events[0] {
+            Ok(Event::Reconnecting { attempt, .. }) => assert_eq!(attempt, 1),
+            ref e => panic!("unexpected event: {:?}", e),
+        }
+        match events[1] {
+            Err(Error::Http(status, _)) => assert_eq!(status.as_u16(), 500),
+            ref e => panic!("unexpected event: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn max_attempts_closed() {
+        let connector = connector(vec![Ok(vec![]), Ok(vec![]), Ok(vec![Ok(1)])]);
+        let policy = ReconnectPolicy {
+            max_attempts: Some(2),
+            ..ReconnectPolicy::default()
+        };
+        let stream = ReconnectingStream::new(connector, policy, MockClock::new());
+
+        let events: Vec<_> = block_on_stream(stream).collect();
+        assert_eq!(events.len(), 4);
+        match events[..3] {
+            [Ok(Event::Connected), Ok(Event::Reconnecting {
+                error: None,
+                attempt: 1,
+                ..
+            }), Ok(Event::Connected)] => (),
+            ref e => panic!("unexpected events: {:?}", e),
+        }
+        match events[3] {
+            Err(ref e @ Error::MaxAttempts(2)) => assert!(!e.is_retryable()),
+            ref e => panic!("unexpected event: {:?}", e),
+        }
+    }
+}
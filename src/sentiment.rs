@@ -0,0 +1,313 @@
+//! Sentiment analysis of Tweets as they arrive from the Streaming API.
+//!
+//! `TwitterStream::with_sentiment` turns a stream of JSON strings into a stream of Tweets paired
+//! with the scores `SentimentIntensityAnalyzer::polarity_scores` gives to their text. Messages
+//! which are not Tweets are skipped.
+//!
+//! URLs, user mentions and hashtags rarely carry sentiment on their own, but t.co links and
+//! handles can accidentally hit lexicon entries. `SentimentStream::strip_entities` removes them
+//! from the text before it is scored.
+//!
+//! `TwitterStream::with_languages` does the same with one analyzer per language, see the
+//! [`language`](../language/index.html) module.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::language::{Languages, UnsupportedLanguage};
+use crate::message::{Messages, StreamMessage, Tweet};
+use crate::{entities, EntityKind, SentimentIntensityAnalyzer};
+
+/// Scores returned by `SentimentIntensityAnalyzer::polarity_scores`.
+///
+/// `neg`, `neu` and `pos` are the proportions of the text falling in each category and add up
+/// to 1.0 (or are all 0.0 for an empty text). `compound` is the normalized sum of all valences,
+/// between -1.0 (most negative) and 1.0 (most positive).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SentimentScores {
+    /// The proportion of the text that is negative.
+    pub neg: f64,
+    /// The proportion of the text that is neutral.
+    pub neu: f64,
+    /// The proportion of the text that is positive.
+    pub pos: f64,
+    /// The normalized, weighted composite score.
+    pub compound: f64,
+}
+
+/// The overall sentiment of a text, derived from its `compound` score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Label {
+    /// The compound score is at or above the positive threshold.
+    Positive,
+    /// The compound score is between the two thresholds.
+    Neutral,
+    /// The compound score is at or below the negative threshold.
+    Negative,
+}
+
+/// Compound score thresholds used by `SentimentScores::label_with`.
+///
+/// The default thresholds are the ones suggested by the VADER authors: 0.05 and -0.05.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// Compound scores at or above this value are `Label::Positive`.
+    pub positive: f64,
+    /// Compound scores at or below this value are `Label::Negative`.
+    pub negative: f64,
+}
+
+impl SentimentScores {
+    /// Classifies the scores with the default `Thresholds`.
+    pub fn label(&self) -> Label {
+        self.label_with(&Thresholds::default())
+    }
+
+    /// Classifies the scores with the given thresholds.
+    pub fn label_with(&self, thresholds: &Thresholds) -> Label {
+        if self.compound >= thresholds.positive {
+            Label::Positive
+        } else if self.compound <= thresholds.negative {
+            Label::Negative
+        } else {
+            Label::Neutral
+        }
+    }
+}
+
+impl From<SentimentScores> for HashMap<&'static str, f64> {
+    /// Converts the scores to the map form returned by earlier versions of this crate.
+    fn from(scores: SentimentScores) -> Self {
+        hashmap![
+            "neg" => scores.neg,
+            "neu" => scores.neu,
+            "pos" => scores.pos,
+            "compound" => scores.compound,
+        ]
+    }
+}
+
+impl Thresholds {
+    pub fn new(positive: f64, negative: f64) -> Self {
+        Thresholds { positive, negative }
+    }
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds::new(0.05, -0.05)
+    }
+}
+
+/// A stream adapter returned by `TwitterStream::with_sentiment`.
+/// It yields Tweets along with the sentiment scores of their text.
+pub struct SentimentStream<'a> {
+    inner: Messages,
+    analyzer: SentimentIntensityAnalyzer<'a>,
+    strip: Vec<EntityKind>,
+}
+
+impl<'a> SentimentStream<'a> {
+    pub(crate) fn new(inner: Messages, analyzer: SentimentIntensityAnalyzer<'a>) -> Self {
+        SentimentStream {
+            inner,
+            analyzer,
+            strip: Vec::new(),
+        }
+    }
+
+    /// Remove entities of the given kinds from the text of each Tweet before scoring it.
+    ///
+    /// Entities are located with [`entities`](../fn.entities.html). Passing an empty slice
+    /// scores the text as is, which is the default.
+    pub fn strip_entities(mut self, kinds: &[EntityKind]) -> Self {
+        self.strip = kinds.to_vec();
+        self
+    }
+
+    /// Returns the analyzer used to score the Tweets.
+    pub fn analyzer(&self) -> &SentimentIntensityAnalyzer<'a> {
+        &self.analyzer
+    }
+}
+
+impl<'a> Stream for SentimentStream<'a> {
+    type Item = Result<(Tweet, SentimentScores), Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let StreamMessage::Tweet(tweet) = ready_some!(self.inner.poll_next_unpin(cx))? {
+                let scores = score(&self.analyzer, &self.strip, &tweet);
+                return Poll::Ready(Some(Ok((tweet, scores))));
+            }
+        }
+    }
+}
+
+/// A stream adapter returned by `TwitterStream::with_languages`.
+/// It yields Tweets along with the sentiment scores of their text, computed by the analyzer of
+/// their language, or an `UnsupportedLanguage` error if there is no such analyzer.
+pub struct LanguageStream<'a> {
+    inner: Messages,
+    languages: Languages<'a>,
+    strip: Vec<EntityKind>,
+}
+
+impl<'a> LanguageStream<'a> {
+    pub(crate) fn new(inner: Messages, languages: Languages<'a>) -> Self {
+        LanguageStream {
+            inner,
+            languages,
+            strip: Vec::new(),
+        }
+    }
+
+    /// Remove entities of the given kinds from the text of each Tweet before scoring it.
+    ///
+    /// See `SentimentStream::strip_entities`.
+    pub fn strip_entities(mut self, kinds: &[EntityKind]) -> Self {
+        self.strip = kinds.to_vec();
+        self
+    }
+
+    /// Returns the analyzers used to score the Tweets.
+    pub fn languages(&self) -> &Languages<'a> {
+        &self.languages
+    }
+}
+
+impl<'a> Stream for LanguageStream<'a> {
+    type Item = Result<(Tweet, Result<SentimentScores, UnsupportedLanguage>), Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let StreamMessage::Tweet(tweet) = ready_some!(self.inner.poll_next_unpin(cx))? {
+                let scores = self
+                    .languages
+                    .for_tweet(tweet.lang.as_ref().map(String::as_str))
+                    .map(|analyzer| score(analyzer, &self.strip, &tweet));
+                return Poll::Ready(Some(Ok((tweet, scores))));
+            }
+        }
+    }
+}
+
+/// Returns `text` with all entities of the given kinds removed.
+pub fn strip_entities(text: &str, kinds: &[EntityKind]) -> String {
+    let mut found: Vec<_> = entities(text)
+        .into_iter()
+        .filter(|e| kinds.contains(&e.kind))
+        .map(|e| e.range)
+        .collect();
+    found.sort();
+
+    let mut output = String::with_capacity(text.len());
+    let mut last_pos = 0;
+    for (start, end) in found {
+        if start < last_pos {
+            continue;
+        }
+        output.push_str(&text[last_pos..start]);
+        last_pos = end;
+    }
+    output.push_str(&text[last_pos..]);
+
+    output
+}
+
+fn score(
+    analyzer: &SentimentIntensityAnalyzer<'_>,
+    strip: &[EntityKind],
+    tweet: &Tweet,
+) -> SentimentScores {
+    let text = tweet.full_text();
+    if strip.is_empty() {
+        analyzer.polarity_scores(text)
+    } else {
+        analyzer.polarity_scores(&strip_entities(text, strip))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn compound(compound: f64) -> SentimentScores {
+        SentimentScores {
+            compound,
+            ..SentimentScores::default()
+        }
+    }
+
+    #[test]
+    fn label() {
+        assert_eq!(compound(0.05).label(), Label::Positive);
+        assert_eq!(compound(0.049).label(), Label::Neutral);
+        assert_eq!(compound(0.0).label(), Label::Neutral);
+        assert_eq!(compound(-0.049).label(), Label::Neutral);
+        assert_eq!(compound(-0.05).label(), Label::Negative);
+
+        let thresholds = Thresholds::new(0.5, -0.25);
+        assert_eq!(compound(0.5).label_with(&thresholds), Label::Positive);
+        assert_eq!(compound(0.3).label_with(&thresholds), Label::Neutral);
+        assert_eq!(compound(-0.25).label_with(&thresholds), Label::Negative);
+    }
+
+    #[test]
+    fn map() {
+        let scores = SentimentScores {
+            neg: 0.1,
+            neu: 0.6,
+            pos: 0.3,
+            compound: 0.4215,
+        };
+        let map = HashMap::from(scores);
+        assert_eq!(map.len(), 4);
+        let from_map = SentimentScores {
+            neg: map["neg"],
+            neu: map["neu"],
+            pos: map["pos"],
+            compound: map["compound"],
+        };
+        assert_eq!(from_map, scores);
+
+        let json = serde_json::to_string(&scores).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SentimentScores>(&json).unwrap(),
+            scores
+        );
+    }
+    #[test]
+    fn strip() {
+        let text = "Rust is great https://t.co/abc @ferris #happy";
+        assert_eq!(
+            strip_entities(
+                text,
+                &[EntityKind::Url, EntityKind::ScreenName, EntityKind::Hashtag]
+            ),
+            "Rust is great   "
+        );
+        assert_eq!(
+            strip_entities(text, &[EntityKind::Url]),
+            "Rust is great  @ferris #happy"
+        );
+        assert_eq!(
+            strip_entities(text, &[EntityKind::ScreenName, EntityKind::Hashtag]),
+            "Rust is great https://t.co/abc  "
+        );
+        assert_eq!(strip_entities(text, &[]), text);
+
+        let plain = "Nothing to see here.";
+        assert_eq!(
+            strip_entities(plain, &[EntityKind::Url, EntityKind::Hashtag]),
+            plain
+        );
+        assert_eq!(strip_entities("", &[EntityKind::Url]), "");
+    }
+}
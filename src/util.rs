@@ -1,3 +1,4 @@
+use std::cmp;
 use std::fmt::{self, Display, Formatter};
 use std::future::Future;
 use std::mem;
@@ -103,6 +104,14 @@ pub struct Lines<S> {
 /// Wrapper to map `T::Error` onto `Error`.
 pub struct MapErr<T>(T);
 
+/// A future reading the start of a body, up to `limit` bytes.
+/// An error while reading ends the body early instead of failing the future.
+pub struct ReadPrefix<S> {
+    stream: S,
+    buf: BytesMut,
+    limit: usize,
+}
+
 pub trait IntoError {
     fn into_error(self) -> Error;
 }
@@ -161,6 +170,38 @@ where
     }
 }
 
+impl<S> ReadPrefix<S> {
+    pub fn new(stream: S, limit: usize) -> Self {
+        ReadPrefix {
+            stream,
+            buf: BytesMut::new(),
+            limit,
+        }
+    }
+}
+
+impl<S: TryStream + Unpin> Future for ReadPrefix<S>
+where
+    S::Ok: Into<Bytes>,
+{
+    type Output = Bytes;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Bytes> {
+        let this = &mut *self;
+        while this.buf.len() < this.limit {
+            match ready!(this.stream.try_poll_next_unpin(cx)) {
+                Some(Ok(chunk)) => {
+                    let chunk: Bytes = chunk.into();
+                    let n = cmp::min(chunk.len(), this.limit - this.buf.len());
+                    this.buf.extend_from_slice(&chunk[..n]);
+                }
+                Some(Err(_)) | None => break,
+            }
+        }
+        Poll::Ready(mem::replace(&mut this.buf, BytesMut::new()).freeze())
+    }
+}
+
 impl<F: TryFuture + Unpin> Future for MapErr<F>
 where
     F::Error: IntoError,
@@ -257,50 +298,58 @@ cfg_if! {
     if #[cfg(feature = "runtime")] {
         use std::time::Duration;
 
-        use futures_util::future::{self, Either, FutureExt};
-        use futures_util::try_future::IntoFuture;
-
-        pub struct Flatten<S>(IntoStream<S>);
+        use futures_util::future::FutureExt;
+        use tokio_timer::Delay;
+
+        /// The timeouts of a connection to a stream. `None` disables the respective timeout.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct Timeouts {
+            /// The time to establish the connection and receive the response headers.
+            pub connect: Option<Duration>,
+            /// The time between the response headers and the first chunk of the body.
+            pub first_byte: Option<Duration>,
+            /// The time between two chunks of the body.
+            pub inactivity: Option<Duration>,
+        }
 
-        /// A `tokio_timer::Timeout`-like type that holds the original `Duration`
-        /// which can be used to create another `Timeout`.
+        /// A future which fails with `Error::ConnectTimeout` if it does not complete in time.
+        /// It also carries the `Timeouts` which apply to the response body.
         pub struct Timeout<F> {
-            tokio: tokio_timer::Timeout<IntoFuture<F>>,
-            dur: Duration,
+            inner: F,
+            delay: Option<Delay>,
+            timeouts: Timeouts,
         }
 
-        pub type MaybeTimeout<F> = Either<Timeout<F>, MapErr<F>>;
-
-        pub type MaybeTimeoutStream<S> = Either<
-            Flatten<tokio_timer::Timeout<IntoStream<S>>>, MapErr<S>,
-        >;
-
-        impl<S: TryStream> Flatten<S> {
-            pub fn new(s: S) -> Self {
-                Flatten(s.into_stream())
-            }
+        /// A stream which fails with `Error::FirstByteTimeout` if it does not yield its first
+        /// item in time, or with `Error::InactivityTimeout` if it goes quiet afterwards.
+        pub struct IdleTimeout<S> {
+            inner: S,
+            delay: Option<Delay>,
+            received: bool,
+            timeouts: Timeouts,
         }
 
-        impl<S: TryStream<Ok = Result<T, E>> + Unpin, T, E> Stream for Flatten<S>
-        where
-            S::Error: IntoError,
-            E: IntoError,
-        {
-            type Item = Result<T, Error>;
+        pub type MaybeTimeout<F> = Timeout<F>;
 
-            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-                (&mut self.0)
-                    .map_err(IntoError::into_error)
-                    .and_then(|r| future::ready(r.map_err(IntoError::into_error)))
-                    .poll_next_unpin(cx)
+        pub type MaybeTimeoutStream<S> = IdleTimeout<S>;
+
+        impl Timeouts {
+            pub fn new(dur: impl Into<Option<Duration>>) -> Self {
+                let dur = dur.into();
+                Timeouts {
+                    connect: dur,
+                    first_byte: dur,
+                    inactivity: dur,
+                }
             }
         }
 
-        impl<F: TryFuture> Timeout<F> {
-            pub fn new(fut: F, dur: Duration) -> Self {
-                Timeout {
-                    tokio: tokio_timer::Timeout::new(fut.into_future(), dur),
-                    dur,
+        impl Default for Timeouts {
+            fn default() -> Self {
+                Timeouts {
+                    connect: Some(Duration::from_secs(30)),
+                    first_byte: Some(Duration::from_secs(90)),
+                    inactivity: Some(Duration::from_secs(90)),
                 }
             }
         }
@@ -312,23 +361,59 @@ cfg_if! {
             type Output = Result<F::Ok, Error>;
 
             fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                (&mut self.tokio)
-                    .map_err(IntoError::into_error)
-                    .and_then(|r| future::ready(r.map_err(IntoError::into_error)))
-                    .poll_unpin(cx)
+                if let Poll::Ready(result) = self.inner.try_poll_unpin(cx) {
+                    return Poll::Ready(result.map_err(IntoError::into_error));
+                }
+
+                let dur = match self.timeouts.connect {
+                    Some(dur) => dur,
+                    None => return Poll::Pending,
+                };
+                let delay = self
+                    .delay
+                    .get_or_insert_with(|| tokio_timer::delay_for(dur));
+                ready!(delay.poll_unpin(cx));
+                Poll::Ready(Err(Error::ConnectTimeout(dur)))
             }
         }
 
-        impl IntoError for tokio_timer::timeout::Elapsed {
-            fn into_error(self) -> Error {
-                Error::TimedOut
+        impl<S: TryStream + Unpin> Stream for IdleTimeout<S>
+        where
+            S::Error: IntoError,
+        {
+            type Item = Result<S::Ok, Error>;
+
+            fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+                if let Poll::Ready(item) = self.inner.try_poll_next_unpin(cx) {
+                    self.received = true;
+                    // Restart the timer from the next poll on.
+                    self.delay = None;
+                    return Poll::Ready(item.map(|r| r.map_err(IntoError::into_error)));
+                }
+
+                let (dur, error): (_, fn(Duration) -> Error) = if self.received {
+                    (self.timeouts.inactivity, Error::InactivityTimeout)
+                } else {
+                    (self.timeouts.first_byte, Error::FirstByteTimeout)
+                };
+                let dur = match dur {
+                    Some(dur) => dur,
+                    None => return Poll::Pending,
+                };
+                let delay = self
+                    .delay
+                    .get_or_insert_with(|| tokio_timer::delay_for(dur));
+                ready!(delay.poll_unpin(cx));
+                self.delay = None;
+                Poll::Ready(Some(Err(error(dur))))
             }
         }
 
-        pub fn timeout<F: TryFuture>(fut: F, dur: Option<Duration>) -> MaybeTimeout<F> {
-            match dur {
-                Some(dur) => Either::Left(Timeout::new(fut, dur)),
-                None => Either::Right(MapErr(fut)),
+        pub fn timeout<F: TryFuture>(fut: F, timeouts: Timeouts) -> MaybeTimeout<F> {
+            Timeout {
+                inner: fut,
+                delay: None,
+                timeouts,
             }
         }
 
@@ -336,12 +421,11 @@ cfg_if! {
         where
             S: TryStream,
         {
-            match *timeout {
-                Either::Left(ref t) => {
-                    let timeout = tokio_timer::Timeout::new(s.into_stream(), t.dur);
-                    Either::Left(Flatten::new(timeout))
-                }
-                Either::Right(_) => Either::Right(MapErr(s)),
+            IdleTimeout {
+                inner: s,
+                delay: None,
+                received: false,
+                timeouts: timeout.timeouts,
             }
         }
     } else {
@@ -392,5 +476,28 @@ mod test {
 
         assert_eq!(lines.collect::<Vec<_>>(), expected.collect::<Vec<_>>());
     }
+
+    #[test]
+    fn read_prefix() {
+        let body = || {
+            let chunks: Vec<Result<_, Error>> = vec![
+                Ok(Bytes::from_static(b"abc")),
+                Ok(Bytes::from_static(b"def")),
+            ];
+            stream::iter(chunks)
+        };
+
+        let prefix = futures_executor::block_on(ReadPrefix::new(body(), 4));
+        assert_eq!(&prefix[..], b"abcd");
+        let prefix = futures_executor::block_on(ReadPrefix::new(body(), 10));
+        assert_eq!(&prefix[..], b"abcdef");
+
+        let failing = stream::iter(vec![
+            Ok(Bytes::from_static(b"abc")),
+            Err(Error::custom("connection reset")),
+        ]);
+        let prefix = futures_executor::block_on(ReadPrefix::new(failing, 10));
+        assert_eq!(&prefix[..], b"abc");
+    }
 }
 
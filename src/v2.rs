@@ -0,0 +1,582 @@
+//! Access to the filtered stream of the Twitter API v2.
+//!
+//! Unlike the v1.1 Streaming API, the v2 filtered stream authenticates with an app-only bearer
+//! token (see `auth::Bearer`) and is not configured through request parameters: the Tweets it
+//! delivers are the ones matching a set of rules stored by Twitter, which are managed with
+//! `Rules`.
+//!
+//! `FilteredStreamBuilder::listen_with_client` resolves to the same `TwitterStream` as
+//! `StreamBuilder::listen_with_client`. Its `messages` yield `StreamMessage::Payload`s holding
+//! the Tweet, the objects requested with `expansions` and the rules the Tweet matched.
+//!
+//! See the [Twitter Developer Documentation][1] for more information.
+//!
+//! [1]: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/introduction
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::auth::Bearer;
+//! use khipu::v2::{FilteredStreamBuilder, NewRule, Rules};
+//! use khipu::StreamMessage;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), khipu::Error> {
+//! let client = hyper::Client::builder().build(hyper_tls::HttpsConnector::new().unwrap());
+//! let token = Bearer::new("AAAAAAAAAAAAAAAAAAAAA...");
+//!
+//! let rules = Rules::new(&token);
+//! rules
+//!     .add(&client, &[NewRule::new("rustlang lang:en").tag("rust")])
+//!     .await?;
+//!
+//! let mut stream = FilteredStreamBuilder::new(&token)
+//!     .tweet_fields(&["created_at", "lang"][..])
+//!     .expansions(&["author_id"][..])
+//!     .listen_with_client(&client)
+//!     .await?
+//!     .messages();
+//!
+//! while let Some(msg) = stream.try_next().await? {
+//!     if let StreamMessage::Payload(payload) = msg {
+//!         println!("{}: {}", payload.data.id, payload.data.text);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::{self, Formatter};
+#[cfg(feature = "runtime")]
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use hyper::body::{Body, Payload as HttpPayload};
+use hyper::client::connect::Connect;
+use hyper::client::Client;
+use hyper::header::{HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_TYPE};
+use hyper::Request;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Authenticate, Bearer};
+use crate::decode::ACCEPTED_ENCODINGS;
+use crate::error::Error;
+use crate::filters::{RequestMethod, Uri};
+use crate::util::*;
+use crate::FutureTwitterStream;
+
+const STREAM_URI: &str = "https://api.twitter.com/2/tweets/search/stream";
+const RULES_URI: &str = "https://api.twitter.com/2/tweets/search/stream/rules";
+
+/// A builder for the `GET /2/tweets/search/stream` endpoint.
+#[derive(Clone, Debug)]
+pub struct FilteredStreamBuilder<'a, T = Bearer> {
+    endpoint: Uri,
+    token: T,
+    #[cfg(feature = "runtime")]
+    timeouts: Timeouts,
+    params: StreamParams<'a>,
+}
+
+/// A client for the `/2/tweets/search/stream/rules` endpoint.
+#[derive(Clone, Debug)]
+pub struct Rules<T = Bearer> {
+    endpoint: Uri,
+    token: T,
+    dry_run: bool,
+}
+
+/// A filtering rule stored by Twitter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rule {
+    /// The ID assigned to the rule by Twitter.
+    pub id: String,
+    /// The rule, e.g. `rustlang lang:en -is:retweet`.
+    pub value: String,
+    /// The label given to the rule, if any.
+    pub tag: Option<String>,
+}
+
+/// A rule to be added with `Rules::add`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct NewRule<'a> {
+    pub value: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<&'a str>,
+}
+
+/// The response to a request to the rules endpoint.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RulesResponse {
+    /// The rules listed, added or deleted.
+    #[serde(default)]
+    pub data: Vec<Rule>,
+    pub meta: RulesMeta,
+    /// The rules that could not be added or deleted.
+    #[serde(default)]
+    pub errors: Vec<RuleError>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RulesMeta {
+    /// When the response was sent.
+    pub sent: DateTime<Utc>,
+    /// The outcome of an addition or deletion. `None` when listing rules.
+    pub summary: Option<RulesSummary>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RulesSummary {
+    pub created: u64,
+    pub not_created: u64,
+    pub deleted: u64,
+    pub not_deleted: u64,
+    pub valid: u64,
+    pub invalid: u64,
+}
+
+/// A rule that could not be added or deleted.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct RuleError {
+    /// The value of the rule, for additions.
+    pub value: Option<String>,
+    /// The ID of the rule, for deletions and duplicate rules.
+    pub id: Option<String>,
+    pub title: String,
+    /// A URI identifying the type of the error.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub details: Vec<String>,
+}
+
+/// A message of the v2 filtered stream.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Payload {
+    pub data: Tweet,
+    /// The objects requested with `FilteredStreamBuilder::expansions`.
+    #[serde(default)]
+    pub includes: Includes,
+    /// The rules the Tweet matched.
+    #[serde(default)]
+    pub matching_rules: Vec<MatchingRule>,
+}
+
+/// A Tweet, as represented by the Twitter API v2.
+///
+/// Fields other than `id` and `text` are only present if requested with
+/// `FilteredStreamBuilder::tweet_fields`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Tweet {
+    pub id: String,
+    pub text: String,
+    pub author_id: Option<String>,
+    pub conversation_id: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub in_reply_to_user_id: Option<String>,
+    pub lang: Option<String>,
+    pub possibly_sensitive: Option<bool>,
+}
+
+/// A user, as represented by the Twitter API v2.
+#[derive(Clone, Debug, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub location: Option<String>,
+    pub verified: Option<bool>,
+}
+
+/// Objects referenced by a Tweet and requested with `FilteredStreamBuilder::expansions`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Includes {
+    pub tweets: Vec<Tweet>,
+    pub users: Vec<User>,
+}
+
+/// A rule matched by a Tweet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub struct MatchingRule {
+    pub id: String,
+    pub tag: Option<String>,
+}
+
+#[derive(Clone, Debug, oauth::Authorize)]
+struct StreamParams<'a> {
+    #[oauth1(encoded, fmt = "fmt_fields")]
+    expansions: Option<&'a [&'a str]>,
+    #[oauth1(encoded, fmt = "fmt_fields", rename = "tweet.fields")]
+    tweet_fields: Option<&'a [&'a str]>,
+    #[oauth1(encoded, fmt = "fmt_fields", rename = "user.fields")]
+    user_fields: Option<&'a [&'a str]>,
+}
+
+#[derive(oauth::Authorize)]
+struct RulesParams {
+    #[oauth1(skip_if = "not")]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RulesRequest<'a> {
+    Add(&'a [NewRule<'a>]),
+    Delete { ids: &'a [&'a str] },
+}
+
+impl<'a, T: Authenticate> FilteredStreamBuilder<'a, T> {
+    /// Create a builder for the filtered stream, authenticating with an app-only bearer token.
+    pub fn new(token: T) -> Self {
+        FilteredStreamBuilder {
+            endpoint: Uri::from_static(STREAM_URI),
+            token,
+            #[cfg(feature = "runtime")]
+            timeouts: Timeouts::default(),
+            params: StreamParams {
+                expansions: None,
+                tweet_fields: None,
+                user_fields: None,
+            },
+        }
+    }
+
+    /// Reset the endpoint, e.g. to connect to a `MockServer`.
+    pub fn endpoint(&mut self, endpoint: Uri) -> &mut Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Set the connect, first-byte and inactivity timeouts at once.
+    /// See `StreamBuilder::timeout`.
+    #[cfg(feature = "runtime")]
+    pub fn timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.timeouts = Timeouts::new(timeout);
+        self
+    }
+
+    /// See `StreamBuilder::connect_timeout`.
+    #[cfg(feature = "runtime")]
+    pub fn connect_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.timeouts.connect = timeout.into();
+        self
+    }
+
+    /// See `StreamBuilder::first_byte_timeout`.
+    #[cfg(feature = "runtime")]
+    pub fn first_byte_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.timeouts.first_byte = timeout.into();
+        self
+    }
+
+    /// See `StreamBuilder::inactivity_timeout`.
+    #[cfg(feature = "runtime")]
+    pub fn inactivity_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.timeouts.inactivity = timeout.into();
+        self
+    }
+
+    /// Set the Tweet fields to deliver in addition to `id` and `text`, e.g. `created_at`.
+    pub fn tweet_fields(&mut self, fields: impl Into<Option<&'a [&'a str]>>) -> &mut Self {
+        self.params.tweet_fields = fields.into();
+        self
+    }
+
+    /// Set the user fields to deliver with the users in `Payload::includes`.
+    pub fn user_fields(&mut self, fields: impl Into<Option<&'a [&'a str]>>) -> &mut Self {
+        self.params.user_fields = fields.into();
+        self
+    }
+
+    /// Set the objects referenced by Tweets to deliver in `Payload::includes`,
+    /// e.g. `author_id` or `referenced_tweets.id`.
+    pub fn expansions(&mut self, expansions: impl Into<Option<&'a [&'a str]>>) -> &mut Self {
+        self.params.expansions = expansions.into();
+        self
+    }
+
+    /// Start listening on the filtered stream, returning a `Future` which resolves
+    /// to a `Stream` yielding JSON messages from the API.
+    #[cfg(feature = "tls")]
+    pub fn listen(&self) -> Result<FutureTwitterStream, crate::error::TlsError> {
+        let conn = hyper_tls::HttpsConnector::new()?;
+        Ok(self.listen_with_client(&Client::builder().build::<_, Body>(conn)))
+    }
+
+    /// Same as `listen` except that it uses `client` to make HTTP request to the endpoint.
+    pub fn listen_with_client<Conn, B>(&self, client: &Client<Conn, B>) -> FutureTwitterStream
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + HttpPayload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let oauth::Request {
+            authorization,
+            data: uri,
+        } = self.token.authorize("GET", &self.endpoint, &self.params);
+
+        let req = Request::get(uri)
+            .header(
+                ACCEPT_ENCODING,
+                HeaderValue::from_static(ACCEPTED_ENCODINGS),
+            )
+            .header(AUTHORIZATION, Bytes::from(authorization))
+            .body(B::default())
+            .unwrap();
+
+        let res = client.request(req);
+        FutureTwitterStream {
+            #[cfg(feature = "runtime")]
+            response: timeout(res, self.timeouts),
+            #[cfg(not(feature = "runtime"))]
+            response: timeout(res),
+            error: None,
+        }
+    }
+}
+
+impl<T: Authenticate> Rules<T> {
+    /// Create a client for the rules endpoint, authenticating with an app-only bearer token.
+    pub fn new(token: T) -> Self {
+        Rules {
+            endpoint: Uri::from_static(RULES_URI),
+            token,
+            dry_run: false,
+        }
+    }
+
+    /// Reset the endpoint, e.g. to connect to a `MockServer`.
+    pub fn endpoint(&mut self, endpoint: Uri) -> &mut Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Validate additions and deletions without applying them.
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// List the rules currently in effect.
+    pub async fn list<Conn, B>(&self, client: &Client<Conn, B>) -> Result<Vec<Rule>, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + HttpPayload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let params = RulesParams { dry_run: false };
+        let res: RulesResponse = self
+            .send(client, RequestMethod::GET, &params, B::default())
+            .await?;
+        Ok(res.data)
+    }
+
+    /// Add rules. Rules which could not be added are listed in `RulesResponse::errors`.
+    pub async fn add<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        rules: &[NewRule<'_>],
+    ) -> Result<RulesResponse, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + HttpPayload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        self.update(client, &RulesRequest::Add(rules)).await
+    }
+
+    /// Delete rules by ID.
+    pub async fn delete<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        ids: &[&str],
+    ) -> Result<RulesResponse, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + HttpPayload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        self.update(client, &RulesRequest::Delete { ids }).await
+    }
+
+    async fn update<Conn, B>(
+        &self,
+        client: &Client<Conn, B>,
+        request: &RulesRequest<'_>,
+    ) -> Result<RulesResponse, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + HttpPayload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+    {
+        let params = RulesParams {
+            dry_run: self.dry_run,
+        };
+        let body = serde_json::to_vec(request)?;
+        self.send(client, RequestMethod::POST, &params, body.into())
+            .await
+    }
+
+    async fn send<Conn, B, R>(
+        &self,
+        client: &Client<Conn, B>,
+        method: RequestMethod,
+        params: &RulesParams,
+        body: B,
+    ) -> Result<R, Error>
+    where
+        Conn: Connect + Sync + 'static,
+        Conn::Transport: 'static,
+        Conn::Future: 'static,
+        B: Default + From<Vec<u8>> + HttpPayload + Unpin + Send + 'static,
+        B::Data: Send + Unpin,
+        R: DeserializeOwned,
+    {
+        // The JSON body is not signed, so the parameters always go in the query.
+        let oauth::Request {
+            authorization,
+            data: uri,
+        } = self
+            .token
+            .authorize(method.as_ref(), &self.endpoint, params);
+
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(AUTHORIZATION, Bytes::from(authorization))
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(body)
+            .unwrap();
+
+        let res = client.request(req).await?;
+        if !res.status().is_success() {
+            return Err(Error::read_response(res).await);
+        }
+        let body = res.into_body().try_concat().await?;
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+impl<'a> NewRule<'a> {
+    pub fn new(value: &'a str) -> Self {
+        NewRule { value, tag: None }
+    }
+
+    /// Set the label given to the rule, reported in `MatchingRule::tag`.
+    pub fn tag(mut self, tag: &'a str) -> Self {
+        self.tag = Some(tag);
+        self
+    }
+}
+
+fn fmt_fields(fields: &[&str], f: &mut Formatter<'_>) -> fmt::Result {
+    fmt_join(fields, "%2C", f)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rules_request() {
+        let rules = [
+            NewRule::new("rustlang lang:en").tag("rust"),
+            NewRule::new("ferris"),
+        ];
+        assert_eq!(
+            serde_json::to_string(&RulesRequest::Add(&rules)).unwrap(),
+            r#"{"add":[{"value":"rustlang lang:en","tag":"rust"},{"value":"ferris"}]}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&RulesRequest::Delete { ids: &["1", "2"] }).unwrap(),
+            r#"{"delete":{"ids":["1","2"]}}"#
+        );
+    }
+
+    #[test]
+    fn rules_response() {
+        let res: RulesResponse = serde_json::from_str(
+            r#"{
+                "data": [{"id": "1273026480692322304", "value": "rustlang lang:en", "tag": "rust"}],
+                "meta": {
+                    "sent": "2020-06-16T22:55:39.356Z",
+                    "summary": {"created": 1, "not_created": 1, "valid": 1, "invalid": 1}
+                },
+                "errors": [{
+                    "value": "(",
+                    "title": "UnprocessableEntity",
+                    "type": "https://api.twitter.com/2/problems/invalid-rules",
+                    "details": ["Unmatched parenthesis."]
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            res.data,
+            [Rule {
+                id: "1273026480692322304".into(),
+                value: "rustlang lang:en".into(),
+                tag: Some("rust".into()),
+            }]
+        );
+        assert_eq!(res.meta.sent.timestamp(), 1592348139);
+        assert_eq!(
+            res.meta.summary,
+            Some(RulesSummary {
+                created: 1,
+                not_created: 1,
+                valid: 1,
+                invalid: 1,
+                ..RulesSummary::default()
+            })
+        );
+        assert_eq!(
+            res.errors,
+            [RuleError {
+                value: Some("(".into()),
+                id: None,
+                title: "UnprocessableEntity".into(),
+                kind: "https://api.twitter.com/2/problems/invalid-rules".into(),
+                details: vec!["Unmatched parenthesis.".into()],
+            }]
+        );
+
+        // Listing rules when there are none.
+        let res: RulesResponse =
+            serde_json::from_str(r#"{"meta":{"sent":"2020-06-16T22:55:39.356Z"}}"#).unwrap();
+        assert!(res.data.is_empty());
+        assert!(res.meta.summary.is_none());
+        assert!(res.errors.is_empty());
+    }
+
+    #[test]
+    fn dry_run() {
+        let token = Bearer::new("AAAA");
+
+        let params = RulesParams { dry_run: true };
+        let req = token.authorize("POST", RULES_URI, &params);
+        assert_eq!(req.data, format!("{}?dry_run=true", RULES_URI));
+
+        let params = RulesParams { dry_run: false };
+        let req = token.authorize("POST", RULES_URI, &params);
+        assert!(!req.data.contains("dry_run"));
+    }
+}
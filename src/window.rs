@@ -0,0 +1,894 @@
+//! Rolling sentiment aggregates of tracked keywords.
+//!
+//! An `Aggregator` keeps statistics of the sentiment scores recorded for each keyword over a set
+//! of windows, by default tumbling and sliding windows of one minute, five minutes and one hour:
+//!
+//! * a `Window::Tumbling` window covers consecutive, non-overlapping periods aligned to the Unix
+//!   epoch, e.g. 12:00 to 12:05, then 12:05 to 12:10. Its statistics are final once a sample of a
+//!   later period is recorded,
+//! * a `Window::Sliding` window covers the given length of time up to the latest sample.
+//!
+//! Windows are driven by the time of the samples, which is the creation time of the Tweets,
+//! rather than the wall clock. Samples older than the current period of a tumbling window or
+//! than the extent of a sliding window are ignored by that window. Call `Aggregator::advance` to
+//! let time pass for keywords which receive no new samples.
+//!
+//! The statistics of a window are its volume, the mean `SentimentScores`, i.e. the mean compound
+//! score and the mean positive, negative and neutral shares, and the tokens which contributed the
+//! most valence. They are available as snapshots from `Aggregator::snapshot` and
+//! `Aggregator::snapshots`, and each update is published to the `Changes` streams returned by
+//! `Aggregator::changes`.
+//!
+//! `Messages::aggregate` scores the Tweets of a stream, matches them to the tracked phrases with a
+//! `TrackMatcher` and records them in an `Aggregator` under each phrase they matched.
+//!
+//! ### Example
+//!
+//! ```rust,no_run
+//! use futures::prelude::*;
+//! use khipu::filters::FilterParams;
+//! use khipu::matcher::TrackMatcher;
+//! use khipu::window::{Aggregator, Change, Window, MINUTE};
+//! use khipu::{Glyph, SentimentIntensityAnalyzer, StreamBuilder};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let glyph: Glyph = unimplemented!();
+//! let mut params = FilterParams::new();
+//! params.track_phrase("rustlang").unwrap();
+//!
+//! let aggregator = Aggregator::new();
+//! let mut changes = aggregator.changes();
+//! tokio::spawn(async move {
+//!     while let Some(change) = changes.next().await {
+//!         if let Change::Closed(snapshot) = change {
+//!             if snapshot.window == Window::Tumbling(MINUTE) {
+//!                 println!("{}: {:.3}", snapshot.keyword, snapshot.stats.mean.compound);
+//!             }
+//!         }
+//!     }
+//! });
+//!
+//! let stream = StreamBuilder::filter(glyph)
+//!     .params(&params)
+//!     .listen()
+//!     .unwrap()
+//!     .await
+//!     .unwrap();
+//! let mut tweets = stream.messages().aggregate(
+//!     SentimentIntensityAnalyzer::new(),
+//!     TrackMatcher::from_params(&params),
+//!     &aggregator,
+//! );
+//! while let Some((matched, scores)) = tweets.try_next().await.unwrap() {
+//!     // ...
+//! #   drop((matched, scores));
+//! }
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::explain::Explanation;
+use crate::matcher::{Matched, TrackMatcher};
+use crate::message::{Messages, StreamMessage};
+use crate::sentiment::SentimentScores;
+use crate::SentimentIntensityAnalyzer;
+
+/// One minute.
+pub const MINUTE: Duration = Duration::from_secs(60);
+/// Five minutes.
+pub const FIVE_MINUTES: Duration = Duration::from_secs(5 * 60);
+/// One hour.
+pub const HOUR: Duration = Duration::from_secs(60 * 60);
+
+const TOP_TOKENS: usize = 10;
+
+/// The extent of time covered by a window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum Window {
+    /// Consecutive, non-overlapping periods of the given length, aligned to the Unix epoch.
+    Tumbling(Duration),
+    /// The given length of time up to the latest sample.
+    Sliding(Duration),
+}
+
+/// The scores of a Tweet recorded in an `Aggregator`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// When the Tweet was created.
+    pub time: DateTime<Utc>,
+    /// The scores of the Tweet.
+    pub scores: SentimentScores,
+    /// The lowercase glyphs of the Tweet which carry sentiment, along with their valence.
+    pub tokens: Vec<(String, f64)>,
+}
+
+/// Statistics of the samples in a window.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WindowStats {
+    /// The beginning of the window.
+    pub start: DateTime<Utc>,
+    /// The end of the window.
+    pub end: DateTime<Utc>,
+    /// The number of samples in the window.
+    pub volume: u64,
+    /// The mean of the scores of the samples, or all zeros for an empty window.
+    pub mean: SentimentScores,
+    /// The standard deviation of the compound scores of the samples.
+    pub compound_stddev: f64,
+    /// The tokens with the largest total valence in absolute value, largest first.
+    pub top_tokens: Vec<TokenStats>,
+}
+
+/// The contribution of a token to the samples of a window.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TokenStats {
+    /// The token.
+    pub token: String,
+    /// The number of occurrences of the token.
+    pub count: u64,
+    /// The total valence of the occurrences.
+    pub valence: f64,
+}
+
+/// The statistics of a window of a keyword.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Snapshot {
+    /// The keyword.
+    pub keyword: String,
+    /// The window.
+    pub window: Window,
+    /// The statistics.
+    pub stats: WindowStats,
+}
+
+/// An update published to the `Changes` streams of an `Aggregator`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Change {
+    /// The statistics of a window changed because a sample was recorded or, for sliding
+    /// windows, because samples fell out of it.
+    Updated(Snapshot),
+    /// A period of a tumbling window ended. The snapshot holds its final statistics.
+    Closed(Snapshot),
+}
+
+/// A handle to rolling statistics of the samples recorded for each keyword.
+///
+/// Clones of an `Aggregator` share the same statistics.
+#[derive(Clone)]
+pub struct Aggregator {
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// A stream of the `Change`s of an `Aggregator`, returned by `Aggregator::changes`.
+/// It ends once all the clones of the `Aggregator` are dropped.
+pub struct Changes {
+    queue: Arc<Mutex<Queue>>,
+}
+
+/// A stream adapter returned by `Messages::aggregate`.
+/// It yields Tweets along with the tracked phrases they matched and the sentiment scores of their
+/// text, skipping other messages.
+pub struct Aggregated<'a, S> {
+    inner: S,
+    analyzer: SentimentIntensityAnalyzer<'a>,
+    matcher: TrackMatcher,
+    aggregator: Aggregator,
+}
+
+struct Shared {
+    windows: Vec<Window>,
+    top_tokens: usize,
+    keywords: HashMap<String, Vec<WindowState>>,
+    subscribers: Vec<Arc<Mutex<Queue>>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    changes: VecDeque<Change>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct WindowState {
+    window: Window,
+    length: chrono::Duration,
+    // The beginning of the current period of a tumbling window.
+    start: Option<DateTime<Utc>>,
+    // The end of a sliding window.
+    latest: Option<DateTime<Utc>>,
+    // The samples of a sliding window, oldest first.
+    samples: VecDeque<Sample>,
+    totals: Totals,
+}
+
+#[derive(Default)]
+struct Totals {
+    volume: u64,
+    neg: f64,
+    neu: f64,
+    pos: f64,
+    compound: f64,
+    compound_sq: f64,
+    tokens: HashMap<String, (u64, f64)>,
+}
+
+impl Window {
+    /// Returns the tumbling and sliding windows of one minute, five minutes and one hour.
+    pub fn standard() -> Vec<Window> {
+        vec![
+            Window::Tumbling(MINUTE),
+            Window::Tumbling(FIVE_MINUTES),
+            Window::Tumbling(HOUR),
+            Window::Sliding(MINUTE),
+            Window::Sliding(FIVE_MINUTES),
+            Window::Sliding(HOUR),
+        ]
+    }
+
+    /// Returns the length of the window.
+    pub fn length(&self) -> Duration {
+        match *self {
+            Window::Tumbling(length) | Window::Sliding(length) => length,
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Window::Tumbling(length) => write!(f, "tumbling {}s", length.as_secs()),
+            Window::Sliding(length) => write!(f, "sliding {}s", length.as_secs()),
+        }
+    }
+}
+
+impl Sample {
+    /// Creates a sample without tokens.
+    pub fn new(time: DateTime<Utc>, scores: SentimentScores) -> Self {
+        Sample {
+            time,
+            scores,
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Creates a sample from the scores and the glyphs of `explanation` which carry sentiment.
+    pub fn from_explanation(time: DateTime<Utc>, explanation: &Explanation) -> Self {
+        let tokens = explanation
+            .glyphs
+            .iter()
+            .filter(|g| g.valence != 0.0)
+            .map(|g| (g.glyph.to_lowercase(), g.valence))
+            .collect();
+        Sample {
+            time,
+            scores: explanation.scores,
+            tokens,
+        }
+    }
+}
+
+impl Aggregator {
+    /// Creates an aggregator with the `Window::standard` windows.
+    pub fn new() -> Self {
+        Aggregator::with_windows(&Window::standard())
+    }
+
+    /// Creates an aggregator with the given windows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a window is shorter than a millisecond.
+    pub fn with_windows(windows: &[Window]) -> Self {
+        for window in windows {
+            assert!(
+                window.length() >= Duration::from_millis(1),
+                "window shorter than a millisecond: {:?}",
+                window
+            );
+        }
+        Aggregator {
+            shared: Arc::new(Mutex::new(Shared {
+                windows: windows.to_vec(),
+                top_tokens: TOP_TOKENS,
+                keywords: HashMap::new(),
+                subscribers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Set the number of tokens listed in `WindowStats::top_tokens`. Defaults to 10.
+    pub fn top_tokens(&mut self, n: usize) -> &mut Self {
+        self.shared.lock().unwrap().top_tokens = n;
+        self
+    }
+
+    /// Returns the windows of the aggregator.
+    pub fn windows(&self) -> Vec<Window> {
+        self.shared.lock().unwrap().windows.clone()
+    }
+
+    /// Returns the keywords which samples were recorded for, in lexicographic order.
+    pub fn keywords(&self) -> Vec<String> {
+        let mut keywords: Vec<_> = self
+            .shared
+            .lock()
+            .unwrap()
+            .keywords
+            .keys()
+            .cloned()
+            .collect();
+        keywords.sort();
+        keywords
+    }
+
+    /// Record a sample for `keyword` in all the windows.
+    pub fn record(&self, keyword: &str, sample: &Sample) {
+        let mut guard = self.shared.lock().unwrap();
+        let shared = &mut *guard;
+        if !shared.keywords.contains_key(keyword) {
+            let states = shared
+                .windows
+                .iter()
+                .map(|&w| WindowState::new(w))
+                .collect();
+            shared.keywords.insert(keyword.to_owned(), states);
+        }
+
+        let mut changes = Vec::new();
+        for state in shared.keywords.get_mut(keyword).unwrap() {
+            state.record(sample, keyword, shared.top_tokens, &mut changes);
+        }
+        shared.publish(changes);
+    }
+
+    /// Let time pass up to `now` for all the keywords: tumbling windows whose period ended are
+    /// closed and samples falling out of sliding windows are discarded.
+    pub fn advance(&self, now: DateTime<Utc>) {
+        let mut shared = self.shared.lock().unwrap();
+        let top_tokens = shared.top_tokens;
+        let mut changes = Vec::new();
+        for (keyword, states) in &mut shared.keywords {
+            for state in states {
+                state.advance(now, keyword, top_tokens, &mut changes);
+            }
+        }
+        shared.publish(changes);
+    }
+
+    /// Forget the statistics of `keyword`, e.g. once it is no longer tracked.
+    pub fn remove(&self, keyword: &str) {
+        self.shared.lock().unwrap().keywords.remove(keyword);
+    }
+
+    /// Returns a snapshot of the statistics of a window of `keyword`, or `None` if no samples
+    /// were recorded for `keyword` or the aggregator has no such window.
+    pub fn snapshot(&self, keyword: &str, window: Window) -> Option<WindowStats> {
+        let shared = self.shared.lock().unwrap();
+        shared
+            .keywords
+            .get(keyword)?
+            .iter()
+            .find(|state| state.window == window)
+            .map(|state| state.stats(shared.top_tokens))
+    }
+
+    /// Returns snapshots of the statistics of all the windows of all the keywords, ordered by
+    /// keyword, then in the order of the windows.
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        let shared = self.shared.lock().unwrap();
+        let top_tokens = shared.top_tokens;
+        let mut keywords: Vec<_> = shared.keywords.iter().collect();
+        keywords.sort_by(|a, b| a.0.cmp(b.0));
+        keywords
+            .into_iter()
+            .flat_map(|(keyword, states)| {
+                states.iter().map(move |state| Snapshot {
+                    keyword: keyword.clone(),
+                    window: state.window,
+                    stats: state.stats(top_tokens),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a stream of the changes to the statistics from now on.
+    ///
+    /// Changes are queued until the stream is polled, so it should be polled regularly.
+    pub fn changes(&self) -> Changes {
+        let queue = Arc::new(Mutex::new(Queue::default()));
+        self.shared.lock().unwrap().subscribers.push(queue.clone());
+        Changes { queue }
+    }
+
+    /// Wrap a stream of messages, recording the scores of each Tweet under the tracked phrases
+    /// it matched.
+    pub fn aggregate<'a, S>(
+        &self,
+        stream: S,
+        analyzer: SentimentIntensityAnalyzer<'a>,
+        matcher: TrackMatcher,
+    ) -> Aggregated<'a, S>
+    where
+        S: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+    {
+        Aggregated {
+            inner: stream,
+            analyzer,
+            matcher,
+            aggregator: self.clone(),
+        }
+    }
+}
+
+impl Default for Aggregator {
+    fn default() -> Self {
+        Aggregator::new()
+    }
+}
+
+impl fmt::Debug for Aggregator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shared = self.shared.lock().unwrap();
+        f.debug_struct("Aggregator")
+            .field("windows", &shared.windows)
+            .field("top_tokens", &shared.top_tokens)
+            .field("keywords", &shared.keywords.len())
+            .finish()
+    }
+}
+
+impl Shared {
+    fn publish(&mut self, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        // Forget the queues whose `Changes` was dropped.
+        self.subscribers
+            .retain(|queue| Arc::strong_count(queue) > 1);
+        for queue in &self.subscribers {
+            let mut queue = queue.lock().unwrap();
+            queue.changes.extend(changes.iter().cloned());
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        for queue in &self.subscribers {
+            let mut queue = queue.lock().unwrap();
+            queue.closed = true;
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl WindowState {
+    fn new(window: Window) -> Self {
+        WindowState {
+            window,
+            length: chrono::Duration::milliseconds(window.length().as_millis() as i64),
+            start: None,
+            latest: None,
+            samples: VecDeque::new(),
+            totals: Totals::default(),
+        }
+    }
+
+    fn record(&mut self, sample: &Sample, keyword: &str, top: usize, changes: &mut Vec<Change>) {
+        match self.window {
+            Window::Tumbling(_) => {
+                let start = self.period_start(sample.time);
+                match self.start {
+                    Some(current) if start < current => return,
+                    Some(current) if start > current => self.close(keyword, top, changes),
+                    _ => (),
+                }
+                self.start = Some(start);
+                self.totals.add(sample);
+            }
+            Window::Sliding(_) => {
+                let latest = self.latest.map_or(sample.time, |t| t.max(sample.time));
+                if sample.time <= latest - self.length {
+                    return;
+                }
+                let pos = self
+                    .samples
+                    .iter()
+                    .rposition(|s| s.time <= sample.time)
+                    .map_or(0, |i| i + 1);
+                self.samples.insert(pos, sample.clone());
+                self.totals.add(sample);
+                self.latest = Some(latest);
+                self.evict();
+            }
+        }
+        changes.push(Change::Updated(self.snapshot(keyword, top)));
+    }
+
+    fn advance(
+        &mut self,
+        now: DateTime<Utc>,
+        keyword: &str,
+        top: usize,
+        changes: &mut Vec<Change>,
+    ) {
+        match self.window {
+            Window::Tumbling(_) => {
+                if let Some(start) = self.start {
+                    if now >= start + self.length {
+                        self.close(keyword, top, changes);
+                        self.start = Some(self.period_start(now));
+                    }
+                }
+            }
+            Window::Sliding(_) => {
+                if let Some(latest) = self.latest {
+                    if now > latest {
+                        self.latest = Some(now);
+                        if self.evict() {
+                            changes.push(Change::Updated(self.snapshot(keyword, top)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publishes the statistics of the current period of a tumbling window and resets them.
+    fn close(&mut self, keyword: &str, top: usize, changes: &mut Vec<Change>) {
+        if self.totals.volume > 0 {
+            changes.push(Change::Closed(self.snapshot(keyword, top)));
+        }
+        self.totals = Totals::default();
+    }
+
+    /// Discards the samples which fell out of a sliding window. Returns whether there were any.
+    fn evict(&mut self) -> bool {
+        let cutoff = match self.latest {
+            Some(latest) => latest - self.length,
+            None => return false,
+        };
+        let len = self.samples.len();
+        while let Some(sample) = self.samples.pop_front() {
+            if sample.time > cutoff {
+                self.samples.push_front(sample);
+                break;
+            }
+            self.totals.remove(&sample);
+        }
+        self.samples.len() < len
+    }
+
+    fn period_start(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let length = self.length.num_milliseconds();
+        let millis = time.timestamp_millis();
+        Utc.timestamp_millis(millis - millis.rem_euclid(length))
+    }
+
+    fn snapshot(&self, keyword: &str, top: usize) -> Snapshot {
+        Snapshot {
+            keyword: keyword.to_owned(),
+            window: self.window,
+            stats: self.stats(top),
+        }
+    }
+
+    fn stats(&self, top: usize) -> WindowStats {
+        let (start, end) = match self.window {
+            Window::Tumbling(_) => {
+                let start = self.start.unwrap_or_else(|| Utc.timestamp(0, 0));
+                (start, start + self.length)
+            }
+            Window::Sliding(_) => {
+                let end = self.latest.unwrap_or_else(|| Utc.timestamp(0, 0));
+                (end - self.length, end)
+            }
+        };
+        self.totals.stats(start, end, top)
+    }
+}
+
+impl Totals {
+    fn add(&mut self, sample: &Sample) {
+        let scores = &sample.scores;
+        self.volume += 1;
+        self.neg += scores.neg;
+        self.neu += scores.neu;
+        self.pos += scores.pos;
+        self.compound += scores.compound;
+        self.compound_sq += scores.compound * scores.compound;
+        for &(ref token, valence) in &sample.tokens {
+            let entry = self.tokens.entry(token.clone()).or_insert((0, 0.0));
+            entry.0 += 1;
+            entry.1 += valence;
+        }
+    }
+
+    fn remove(&mut self, sample: &Sample) {
+        let scores = &sample.scores;
+        self.volume -= 1;
+        if self.volume == 0 {
+            // Start over rather than keeping rounding errors around.
+            *self = Totals::default();
+            return;
+        }
+        self.neg -= scores.neg;
+        self.neu -= scores.neu;
+        self.pos -= scores.pos;
+        self.compound -= scores.compound;
+        self.compound_sq -= scores.compound * scores.compound;
+        for &(ref token, valence) in &sample.tokens {
+            let remove = match self.tokens.get_mut(token) {
+                Some(entry) => {
+                    entry.0 -= 1;
+                    entry.1 -= valence;
+                    entry.0 == 0
+                }
+                None => false,
+            };
+            if remove {
+                self.tokens.remove(token);
+            }
+        }
+    }
+
+    fn stats(&self, start: DateTime<Utc>, end: DateTime<Utc>, top: usize) -> WindowStats {
+        if self.volume == 0 {
+            return WindowStats {
+                start,
+                end,
+                volume: 0,
+                mean: SentimentScores::default(),
+                compound_stddev: 0.0,
+                top_tokens: Vec::new(),
+            };
+        }
+
+        let n = self.volume as f64;
+        let mean = SentimentScores {
+            neg: self.neg / n,
+            neu: self.neu / n,
+            pos: self.pos / n,
+            compound: self.compound / n,
+        };
+        let variance = (self.compound_sq / n - mean.compound * mean.compound).max(0.0);
+
+        let mut top_tokens: Vec<_> = self
+            .tokens
+            .iter()
+            .map(|(token, &(count, valence))| TokenStats {
+                token: token.clone(),
+                count,
+                valence,
+            })
+            .collect();
+        top_tokens.sort_by(|a, b| {
+            b.valence
+                .abs()
+                .partial_cmp(&a.valence.abs())
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.token.cmp(&b.token))
+        });
+        top_tokens.truncate(top);
+
+        WindowStats {
+            start,
+            end,
+            volume: self.volume,
+            mean,
+            compound_stddev: variance.sqrt(),
+            top_tokens,
+        }
+    }
+}
+
+impl Changes {
+    /// Returns the next queued change, if any, without waiting.
+    pub fn try_recv(&mut self) -> Option<Change> {
+        self.queue.lock().unwrap().changes.pop_front()
+    }
+}
+
+impl Stream for Changes {
+    type Item = Change;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Change>> {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(change) = queue.changes.pop_front() {
+            Poll::Ready(Some(change))
+        } else if queue.closed {
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, S> Aggregated<'a, S> {
+    /// Returns the `Aggregator` updated by this stream.
+    pub fn aggregator(&self) -> &Aggregator {
+        &self.aggregator
+    }
+
+    /// Returns the matcher used to find the tracked phrases of the Tweets.
+    pub fn matcher(&self) -> &TrackMatcher {
+        &self.matcher
+    }
+}
+
+impl<'a, S> Stream for Aggregated<'a, S>
+where
+    S: Stream<Item = Result<StreamMessage, Error>> + Unpin,
+{
+    type Item = Result<(Matched, SentimentScores), Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let StreamMessage::Tweet(tweet) = ready_some!(self.inner.poll_next_unpin(cx))? {
+                let matched = self.matcher.annotate(tweet);
+                let explanation = self
+                    .analyzer
+                    .polarity_scores_explained(matched.tweet.full_text());
+                let sample = Sample::from_explanation(matched.tweet.created_at, &explanation);
+                for phrase in &matched.phrases {
+                    self.aggregator.record(phrase, &sample);
+                }
+                return Poll::Ready(Some(Ok((matched, explanation.scores))));
+            }
+        }
+    }
+}
+
+impl Messages {
+    /// Turn the stream into one yielding Tweets along with the tracked phrases they matched and
+    /// the scores `analyzer` gives to their text, recording the scores in `aggregator`.
+    pub fn aggregate<'a>(
+        self,
+        analyzer: SentimentIntensityAnalyzer<'a>,
+        matcher: TrackMatcher,
+        aggregator: &Aggregator,
+    ) -> Aggregated<'a, Self> {
+        aggregator.aggregate(self, analyzer, matcher)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures_executor::block_on_stream;
+    use futures_util::stream;
+
+    use crate::message::tweet;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp(secs, 0)
+    }
+
+    fn sample(secs: i64, compound: f64, tokens: &[(&str, f64)]) -> Sample {
+        let scores = SentimentScores {
+            compound,
+            ..Default::default()
+        };
+        let mut sample = Sample::new(at(secs), scores);
+        sample.tokens = tokens.iter().map(|&(t, v)| (t.to_owned(), v)).collect();
+        sample
+    }
+
+    #[test]
+    fn tumbling() {
+        let aggregator = Aggregator::with_windows(&[Window::Tumbling(MINUTE)]);
+        let changes = aggregator.changes();
+
+        aggregator.record("rust", &sample(0, 0.5, &[]));
+        aggregator.record("rust", &sample(30, -0.1, &[]));
+        aggregator.record("rust", &sample(70, 0.2, &[]));
+        // Late samples are ignored.
+        aggregator.record("rust", &sample(10, 1.0, &[]));
+        aggregator.advance(at(130));
+        drop(aggregator);
+
+        let changes: Vec<_> = block_on_stream(changes).collect();
+        assert_eq!(changes.len(), 5);
+        match changes[2] {
+            Change::Closed(ref snapshot) => {
+                assert_eq!(snapshot.keyword, "rust");
+                assert_eq!(snapshot.stats.start, at(0));
+                assert_eq!(snapshot.stats.end, at(60));
+                assert_eq!(snapshot.stats.volume, 2);
+                assert!((snapshot.stats.mean.compound - 0.2).abs() < 1e-9);
+                assert!((snapshot.stats.compound_stddev - 0.3).abs() < 1e-9);
+            }
+            ref c => panic!("expected Closed, got {:?}", c),
+        }
+        match changes[4] {
+            Change::Closed(ref snapshot) => {
+                assert_eq!(snapshot.stats.start, at(60));
+                assert_eq!(snapshot.stats.volume, 1);
+            }
+            ref c => panic!("expected Closed, got {:?}", c),
+        }
+    }
+
+    #[test]
+    fn sliding() {
+        let window = Window::Sliding(MINUTE);
+        let aggregator = Aggregator::with_windows(&[window]);
+
+        aggregator.record("rust", &sample(0, 0.5, &[("good", 1.9)]));
+        aggregator.record("rust", &sample(30, -0.5, &[("bad", -2.5)]));
+        aggregator.record("rust", &sample(20, 0.5, &[("good", 1.9)]));
+        aggregator.record("rust", &sample(70, 0.1, &[]));
+
+        let stats = aggregator.snapshot("rust", window).unwrap();
+        assert_eq!(stats.start, at(10));
+        assert_eq!(stats.end, at(70));
+        assert_eq!(stats.volume, 3);
+        assert!((stats.mean.compound - 0.1 / 3.0).abs() < 1e-9);
+        let tokens: Vec<_> = stats.top_tokens.iter().map(|t| &*t.token).collect();
+        assert_eq!(tokens, ["bad", "good"]);
+        assert_eq!(stats.top_tokens[1].count, 1);
+
+        aggregator.advance(at(200));
+        let stats = aggregator.snapshot("rust", window).unwrap();
+        assert_eq!(stats.volume, 0);
+        assert!(stats.top_tokens.is_empty());
+
+        assert_eq!(aggregator.snapshot("rust", Window::Sliding(HOUR)), None);
+        assert_eq!(aggregator.snapshot("go", window), None);
+    }
+
+    #[test]
+    fn aggregate() {
+        let message = |id: u64, text: &str| -> Result<StreamMessage, Error> {
+            Ok(StreamMessage::Tweet(tweet(id, text)))
+        };
+
+        let aggregator = Aggregator::new();
+        let stream = stream::iter(vec![
+            message(1, "Rust is great"),
+            message(2, "Rust is terrible"),
+            message(3, "Nothing to see here"),
+        ]);
+        let matcher = TrackMatcher::new(&["rust", "great"]);
+        let items: Vec<_> = block_on_stream(aggregator.aggregate(
+            stream,
+            SentimentIntensityAnalyzer::new(),
+            matcher,
+        ))
+        .map(Result::unwrap)
+        .collect();
+
+        assert_eq!(items.len(), 3);
+        assert!(items[0].1.compound > 0.0);
+        assert!(items[2].0.phrases.is_empty());
+        assert_eq!(aggregator.keywords(), ["great", "rust"]);
+
+        let stats = aggregator.snapshot("rust", Window::Sliding(HOUR)).unwrap();
+        assert_eq!(stats.volume, 2);
+        let tokens: Vec<_> = stats.top_tokens.iter().map(|t| &*t.token).collect();
+        assert_eq!(tokens, ["great", "terrible"]);
+        assert_eq!(aggregator.snapshots().len(), 12);
+    }
+}
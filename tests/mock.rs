@@ -0,0 +1,508 @@
+#![cfg(feature = "mock")]
+
+use std::time::Duration;
+
+use futures::prelude::*;
+use hyper::client::connect::{Connect, Destination};
+use hyper::client::HttpConnector;
+use hyper::{Client, Uri};
+use khipu::auth::{Bearer, RequestToken};
+use khipu::error::ErrorCode;
+use khipu::filters::{FilterParams, RequestMethod, StatusCode};
+use khipu::mock::{Encoding, MockResponse, MockServer};
+use khipu::rate_limit::RateLimitStatus;
+use khipu::reconnect::{Backoff, Event, ReconnectPolicy};
+use khipu::sentiment::strip_entities;
+use khipu::{
+    Credentials, EntityKind, Error, Glyph, SentimentIntensityAnalyzer, StreamBuilder, StreamMessage,
+};
+
+const ENDPOINT: &str = "/1.1/statuses/filter.json";
+const TWEET: &str = r#"{"created_at":"Wed Oct 10 20:19:24 +0000 2018","id":1050118621198921728,
+"text":"To make room for more expression","user":{"id":6253282,"name":"Twitter API",
+"screen_name":"TwitterAPI"},"lang":"en"}"#;
+
+fn glyph() -> Glyph<&'static str, &'static str> {
+    Glyph::new(
+        "consumer_key",
+        "consumer_secret",
+        "access_key",
+        "access_secret",
+    )
+}
+
+fn tweet() -> String {
+    TWEET.replace('\n', "")
+}
+
+/// Connects to the mock server whatever the destination, so that requests to the fixed
+/// endpoints of the `auth` module reach it.
+#[derive(Clone)]
+struct Redirect {
+    http: HttpConnector,
+    port: u16,
+}
+
+impl Connect for Redirect {
+    type Transport = <HttpConnector as Connect>::Transport;
+    type Error = <HttpConnector as Connect>::Error;
+    type Future = <HttpConnector as Connect>::Future;
+
+    fn connect(&self, mut dst: Destination) -> Self::Future {
+        dst.set_scheme("http").unwrap();
+        dst.set_host("127.0.0.1").unwrap();
+        dst.set_port(self.port);
+        self.http.connect(dst)
+    }
+}
+
+fn redirect(server: &MockServer) -> Client<Redirect> {
+    Client::builder().build(Redirect {
+        http: HttpConnector::new(),
+        port: server.addr().port(),
+    })
+}
+
+#[tokio::test]
+async fn messages() {
+    let server = MockServer::builder()
+        .credentials("consumer_key", "access_key")
+        .respond(
+            MockResponse::new()
+                .keep_alive()
+                .message(&tweet())
+                .keep_alive()
+                .message(r#"{"limit":{"track":42}}"#),
+        )
+        .start()
+        .unwrap();
+
+    let stream = StreamBuilder::custom(RequestMethod::POST, server.uri(ENDPOINT), glyph())
+        .track("rustlang")
+        .listen_with_client(&Client::new())
+        .await
+        .unwrap();
+    let messages: Vec<_> = stream.messages().try_collect().await.unwrap();
+
+    assert_eq!(messages.len(), 2);
+    match messages[0] {
+        StreamMessage::Tweet(ref tweet) => assert_eq!(tweet.user.screen_name, "TwitterAPI"),
+        ref m => panic!("unexpected message: {:?}", m),
+    }
+    match messages[1] {
+        StreamMessage::Limit(ref limit) => assert_eq!(limit.track, 42),
+        ref m => panic!("unexpected message: {:?}", m),
+    }
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].uri.path(), ENDPOINT);
+    assert_eq!(&requests[0].body[..], b"track=rustlang");
+}
+
+#[tokio::test]
+async fn encodings() {
+    for &encoding in &[Encoding::Gzip, Encoding::Deflate, Encoding::Brotli] {
+        let server = MockServer::builder()
+            .respond(
+                MockResponse::new()
+                    .message(&tweet())
+                    .keep_alive()
+                    .message(&tweet())
+                    .encoding(encoding),
+            )
+            .start()
+            .unwrap();
+
+        let stream = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph())
+            .listen_with_client(&Client::new())
+            .await
+            .unwrap();
+        let messages: Vec<_> = stream.messages().try_collect().await.unwrap();
+        assert_eq!(messages.len(), 2, "{:?}", encoding);
+    }
+}
+
+#[tokio::test]
+async fn unsupported_encoding() {
+    let server = MockServer::builder()
+        .respond(
+            MockResponse::new()
+                .header("content-encoding", "compress")
+                .message(&tweet()),
+        )
+        .respond(
+            MockResponse::error(StatusCode::SERVICE_UNAVAILABLE)
+                .header("content-encoding", "compress")
+                .raw("Over capacity"),
+        )
+        .start()
+        .unwrap();
+    let builder = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph());
+
+    match builder.listen_with_client(&Client::new()).await {
+        Err(ref e @ Error::UnsupportedEncoding(_)) => assert!(!e.is_retryable()),
+        _ => panic!("expected an unsupported encoding error"),
+    }
+    // The status of an error response is reported whatever its encoding.
+    match builder.listen_with_client(&Client::new()).await {
+        Err(Error::Http(status, body)) => {
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(&body[..], b"Over capacity");
+        }
+        _ => panic!("expected an HTTP error"),
+    }
+}
+
+#[tokio::test]
+async fn unauthorized() {
+    let server = MockServer::builder()
+        .credentials("other_key", "access_key")
+        .respond(MockResponse::new().message(&tweet()))
+        .start()
+        .unwrap();
+
+    let result = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph())
+        .listen_with_client(&Client::new())
+        .await;
+    match result {
+        Err(Error::TwitterError(status, errors)) => {
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert!(errors.contains(ErrorCode::AuthenticationFailed));
+        }
+        _ => panic!("expected a Twitter error"),
+    }
+    assert_eq!(server.remaining(), 1);
+}
+
+#[tokio::test]
+async fn bearer() {
+    let server = MockServer::builder()
+        .bearer("AAAA")
+        .respond(MockResponse::new().message(&tweet()))
+        .start()
+        .unwrap();
+
+    let stream = StreamBuilder::custom(
+        RequestMethod::GET,
+        server.uri(ENDPOINT),
+        Bearer::new("AAAA"),
+    )
+    .language("en")
+    .listen_with_client(&Client::new())
+    .await
+    .unwrap();
+    let messages: Vec<_> = stream.messages().try_collect().await.unwrap();
+    assert_eq!(messages.len(), 1);
+
+    let requests = server.requests();
+    assert_eq!(requests[0].uri.query(), Some("language=en"));
+    assert_eq!(requests[0].headers["authorization"], "Bearer AAAA");
+
+    let result = StreamBuilder::custom(
+        RequestMethod::GET,
+        server.uri(ENDPOINT),
+        Bearer::new("BBBB"),
+    )
+    .listen_with_client(&Client::new())
+    .await;
+    match result {
+        Err(Error::TwitterError(status, errors)) => {
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert!(errors.contains(ErrorCode::AuthenticationFailed));
+        }
+        _ => panic!("expected a Twitter error"),
+    }
+}
+
+#[tokio::test]
+async fn malformed_rate_limit() {
+    let server = MockServer::builder()
+        .respond(
+            MockResponse::new()
+                .header("x-rate-limit-reset", "soon")
+                .message(&tweet()),
+        )
+        .respond(
+            MockResponse::error(StatusCode::TOO_MANY_REQUESTS)
+                .header("x-rate-limit-remaining", "-1")
+                .header("retry-after", "later"),
+        )
+        .start()
+        .unwrap();
+    let builder = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph());
+
+    let stream = builder.listen_with_client(&Client::new()).await.unwrap();
+    assert_eq!(*stream.rate_limit(), RateLimitStatus::default());
+    let messages: Vec<_> = stream.messages().try_collect().await.unwrap();
+    assert_eq!(messages.len(), 1);
+
+    match builder.listen_with_client(&Client::new()).await {
+        Err(Error::RateLimit(status)) => assert_eq!(status, RateLimitStatus::default()),
+        _ => panic!("expected a rate limit error"),
+    }
+}
+
+#[tokio::test]
+async fn timeouts() {
+    // The stalls are far longer than the timeouts, and the timeouts far longer than the time it
+    // takes to connect, so that the test does not depend on the speed of the machine.
+    let server = MockServer::builder()
+        .respond(MockResponse::new().stall(Duration::from_secs(10)))
+        .respond(
+            MockResponse::new()
+                .message(&tweet())
+                .stall(Duration::from_secs(10)),
+        )
+        .start()
+        .unwrap();
+    let mut builder = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph());
+    builder
+        .first_byte_timeout(Duration::from_millis(500))
+        .inactivity_timeout(Duration::from_millis(200));
+
+    let mut stream = builder.listen_with_client(&Client::new()).await.unwrap();
+    match stream.next().await {
+        Some(Err(Error::FirstByteTimeout(dur))) => assert_eq!(dur, Duration::from_millis(500)),
+        r => panic!("expected a first-byte timeout, got {:?}", r),
+    }
+
+    let mut stream = builder.listen_with_client(&Client::new()).await.unwrap();
+    assert!(stream.next().await.unwrap().is_ok());
+    match stream.next().await {
+        Some(Err(Error::InactivityTimeout(dur))) => assert_eq!(dur, Duration::from_millis(200)),
+        r => panic!("expected an inactivity timeout, got {:?}", r),
+    }
+}
+
+#[tokio::test]
+async fn connect_timeout() {
+    // The connection is queued by the kernel but never accepted, so no response ever comes.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri: Uri = format!("http://{}{}", listener.local_addr().unwrap(), ENDPOINT)
+        .parse()
+        .unwrap();
+
+    let result = StreamBuilder::custom(RequestMethod::GET, uri, glyph())
+        .connect_timeout(Duration::from_millis(500))
+        .listen_with_client(&Client::new())
+        .await;
+    match result {
+        Err(Error::ConnectTimeout(dur)) => assert_eq!(dur, Duration::from_millis(500)),
+        _ => panic!("expected a connect timeout"),
+    }
+}
+
+#[tokio::test]
+async fn reconnect() {
+    let server = MockServer::builder()
+        .respond(MockResponse::error(StatusCode::SERVICE_UNAVAILABLE))
+        .respond(
+            MockResponse::new()
+                .message(&tweet())
+                .stall(Duration::from_millis(10))
+                .disconnect(),
+        )
+        .respond(MockResponse::new().message(&tweet()))
+        .start()
+        .unwrap();
+
+    let backoff = Backoff::Linear {
+        initial: Duration::from_millis(1),
+        step: Duration::from_millis(1),
+        max: Duration::from_millis(10),
+    };
+    let policy = ReconnectPolicy {
+        network: backoff,
+        http: backoff,
+        rate_limit: backoff,
+        max_attempts: None,
+    };
+
+    let events: Vec<_> = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph())
+        .listen_reconnecting_with_client(&Client::new(), policy)
+        .take(6)
+        .map(Result::unwrap)
+        .map(|event| match event {
+            Event::Connected => "connected",
+            Event::Message(_) => "message",
+            Event::Reconnecting { .. } => "reconnecting",
+        })
+        .collect()
+        .await;
+
+    assert_eq!(
+        events,
+        [
+            "reconnecting",
+            "connected",
+            "message",
+            "reconnecting",
+            "connected",
+            "message",
+        ]
+    );
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn sentiment() {
+    let text = "Rust is great https://t.co/abc #happy";
+    let response = || {
+        MockResponse::new()
+            .message(r#"{"limit":{"track":42}}"#)
+            .message(&tweet().replace("To make room for more expression", text))
+            .message(r#"{"friends":[1,2,3]}"#)
+    };
+    let server = MockServer::builder()
+        .respond(response())
+        .respond(response())
+        .start()
+        .unwrap();
+    let builder = StreamBuilder::custom(RequestMethod::GET, server.uri(ENDPOINT), glyph());
+    let analyzer = SentimentIntensityAnalyzer::new();
+
+    // The limit notice and the unknown message are skipped.
+    let stream = builder.listen_with_client(&Client::new()).await.unwrap();
+    let scored: Vec<_> = stream
+        .with_sentiment(SentimentIntensityAnalyzer::new())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(scored.len(), 1);
+    assert_eq!(scored[0].0.text, text);
+    assert_eq!(scored[0].1, analyzer.polarity_scores(text));
+
+    let kinds = [EntityKind::Url, EntityKind::Hashtag];
+    let stream = builder.listen_with_client(&Client::new()).await.unwrap();
+    let scored: Vec<_> = stream
+        .with_sentiment(SentimentIntensityAnalyzer::new())
+        .strip_entities(&kinds)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(scored.len(), 1);
+    assert_eq!(
+        scored[0].1,
+        analyzer.polarity_scores(&strip_entities(text, &kinds))
+    );
+}
+
+#[tokio::test]
+async fn managed() {
+    let tweet_with_id = |id: u64| tweet().replace("1050118621198921728", &id.to_string());
+    // The first connection is dropped at the switch, so the stall only has to be far longer
+    // than it takes to open the second one, and does not slow the test down.
+    let server = MockServer::builder()
+        .respond(
+            MockResponse::new()
+                .message(&tweet_with_id(1))
+                .stall(Duration::from_secs(10))
+                .message(&tweet_with_id(2)),
+        )
+        .respond(
+            MockResponse::new()
+                .message(&tweet_with_id(1))
+                .message(&tweet_with_id(3)),
+        )
+        .start()
+        .unwrap();
+
+    let mut params = FilterParams::new();
+    params.track_phrase("a").unwrap();
+    let mut stream = StreamBuilder::custom(RequestMethod::POST, server.uri(ENDPOINT), glyph())
+        .listen_managed_with_client(&Client::new(), params);
+    let handle = stream.handle();
+
+    let mut ids = Vec::new();
+    while let Some(msg) = stream.try_next().await.unwrap() {
+        if let StreamMessage::Tweet(tweet) = msg {
+            ids.push(tweet.id);
+        }
+        if ids.len() == 1 {
+            let mut params = FilterParams::new();
+            params.track_phrase("b").unwrap();
+            handle.set(params);
+        }
+    }
+
+    // Tweet 1 arrived on both connections, and the first one was dropped before tweet 2.
+    assert_eq!(ids, [1, 3]);
+    assert_eq!(stream.params().track(), "b");
+    let requests = server.requests();
+    assert_eq!(&requests[0].body[..], b"track=a");
+    assert_eq!(&requests[1].body[..], b"track=b");
+}
+
+#[tokio::test]
+async fn three_legged() {
+    let request_token = "oauth_token=request&oauth_token_secret=secret";
+    let access_token = "oauth_token=6253282-access&oauth_token_secret=access_secret&\
+                        user_id=6253282&screen_name=TwitterAPI";
+    let server = MockServer::builder()
+        .respond(MockResponse::new().raw(request_token))
+        .respond(
+            MockResponse::new()
+                .raw(request_token)
+                .raw("&oauth_callback_confirmed=true"),
+        )
+        .respond(MockResponse::new().raw(access_token))
+        .start()
+        .unwrap();
+    let client = redirect(&server);
+    let consumer = Credentials::new("consumer_key", "consumer_secret");
+    let callback = "https://example.com/callback";
+
+    match RequestToken::obtain(&client, consumer, callback).await {
+        Err(Error::MissingValue(name)) => assert_eq!(name, "oauth_callback_confirmed"),
+        r => panic!("expected an unconfirmed callback, got {:?}", r),
+    }
+
+    let token = RequestToken::obtain(&client, consumer, callback)
+        .await
+        .unwrap();
+    assert_eq!(token.token.identifier(), "request");
+    assert_eq!(
+        token.authorize_url(),
+        "https://api.twitter.com/oauth/authorize?oauth_token=request"
+    );
+
+    let denied: Uri = "https://example.com/callback?denied=request"
+        .parse()
+        .unwrap();
+    match token.verifier_from_callback(&denied) {
+        Err(Error::Custom(_)) => {}
+        r => panic!("expected a denied authorization, got {:?}", r),
+    }
+    let mismatched: Uri = "https://example.com/callback?oauth_token=other&oauth_verifier=v"
+        .parse()
+        .unwrap();
+    match token.verifier_from_callback(&mismatched) {
+        Err(Error::Custom(_)) => {}
+        r => panic!("expected a mismatched token, got {:?}", r),
+    }
+    let redirected: Uri = "https://example.com/callback?oauth_token=request&oauth_verifier=v"
+        .parse()
+        .unwrap();
+    let verifier = token.verifier_from_callback(&redirected).unwrap();
+    assert_eq!(verifier, "v");
+
+    let access = token.access_token(&client, &verifier).await.unwrap();
+    assert_eq!(access.user_id, 6253282);
+    assert_eq!(access.screen_name, "TwitterAPI");
+    assert_eq!(access.glyph.client.identifier(), "consumer_key");
+    assert_eq!(access.glyph.glyph.identifier(), "6253282-access");
+    assert_eq!(access.glyph.glyph.secret(), "access_secret");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].uri.path(), "/oauth/request_token");
+    assert!(requests[0].headers["authorization"]
+        .to_str()
+        .unwrap()
+        .contains("oauth_callback=\"https%3A%2F%2Fexample.com%2Fcallback\""));
+    assert_eq!(requests[2].uri.path(), "/oauth/access_token");
+    let authorization = requests[2].headers["authorization"].to_str().unwrap();
+    assert!(authorization.contains("oauth_token=\"request\""));
+    assert!(authorization.contains("oauth_verifier=\"v\""));
+}